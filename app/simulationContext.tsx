@@ -1,8 +1,8 @@
 "use client"
 
-import { Process, Resource } from "@/lib/defs";
-import { invoke } from "@tauri-apps/api/core";
-import React, { createContext, ReactNode, useCallback, useEffect, useMemo, useState } from "react";
+import { AutosaveEntry, Process, Resource, SimulationEventEnvelope, Snapshot, SnapshotDelta } from "@/lib/defs";
+import { invoke, listen } from "@/lib/backend";
+import React, { createContext, ReactNode, useCallback, useEffect, useMemo, useRef, useState } from "react";
 import { EventCallback, EventName, UnlistenFn } from '@tauri-apps/api/event';
 
 type SimulationData = {
@@ -27,6 +27,22 @@ type SimulationData = {
   setResourceToView: (resource: Resource) => void;
 };
 
+// Processes are serialized as { Ready: {...} }, { Blocked: {...} } or { Working: {...} }
+function processId(process: Process): string {
+  return Object.values(process)[0].id;
+}
+
+// Upserts by id, so applying the same delta twice is harmless
+function applyDelta<T>(items: T[], added: T[], changed: T[], removed: string[], id: (item: T) => string): T[] {
+  const updates = new Map([...added, ...changed].map((item) => [id(item), item]));
+  const kept = items
+    .filter((item) => !removed.includes(id(item)))
+    .map((item) => updates.get(id(item)) ?? item);
+  const keptIds = new Set(kept.map(id));
+
+  return [...kept, ...added.filter((item) => !keptIds.has(id(item)))];
+}
+
 interface SimulationProviderProps {
   children: ReactNode;
 }
@@ -86,6 +102,14 @@ export const SimulationProvider = ({ children }: SimulationProviderProps) => {
     }));
   }, []);
 
+  const applySnapshotDelta = useCallback((delta: SnapshotDelta) => {
+    setSimulationData((prev) => ({
+      ...prev,
+      processes: applyDelta(prev.processes, delta.added_processes, delta.changed_processes, delta.removed_processes, processId),
+      resources: applyDelta(prev.resources, delta.added_resources, delta.changed_resources, delta.removed_resources, (resource) => resource.id),
+    }));
+  }, []);
+
   const updateSimulationSpeed = useCallback((newSpeed: number) => {
     setSimulationData((prev) => ({
       ...prev,
@@ -131,7 +155,6 @@ export const SimulationProvider = ({ children }: SimulationProviderProps) => {
   }, []);
 
   async function setupListen() {
-    const listen = (await import('@tauri-apps/api/event')).listen
     setSimulationData((prev) => ({
       ...prev,
       listen: listen,
@@ -174,6 +197,24 @@ export const SimulationProvider = ({ children }: SimulationProviderProps) => {
       })
 
     setupListen()
+
+    // Offer to bring back the last session, in case the app was closed or crashed
+    invoke("autosave_list")
+      .then((entries) => {
+        const latest = (entries as AutosaveEntry[]).at(-1);
+        if (!latest || !window.confirm(`Restore the session autosaved on ${new Date(latest.saved_at).toLocaleString()}?`)) {
+          return;
+        }
+
+        return invoke("autosave_restore", { name: latest.name })
+          .then((snapshot) => {
+            simulationDataValue.updateProcesses((snapshot as Snapshot).processes)
+            simulationDataValue.updateResources((snapshot as Snapshot).resources)
+          })
+      })
+      .catch((error) => {
+        console.error("Error restoring autosave", error)
+      })
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [])
 
@@ -182,23 +223,46 @@ export const SimulationProvider = ({ children }: SimulationProviderProps) => {
     // eslint-disable-next-line react-hooks/exhaustive-deps
   }, [simulationData.simulationSpeed])
 
-  simulationDataValue.listen<string[]>("unsafe_state", (event) => {
-    const _processes: string[] = event.payload;
+  const lastSequence = useRef<number | null>(null);
 
-    // Search for the processes with the ids inside the _processes array
-    const processes: Process[] = simulationDataValue.processes.filter((process) => {
-      return _processes.includes(process.Ready.id);
-    });
+  simulationDataValue.listen<SimulationEventEnvelope>("simulation", (event) => {
+    const { sequence, event: simulationEvent } = event.payload;
 
-    console.log("unsafe_state", event.payload)
+    if (lastSequence.current !== null && sequence > lastSequence.current + 1) {
+      console.warn(`Missed ${sequence - lastSequence.current - 1} simulation events, resyncing`)
+      invoke("simulation_resync").catch((error) => {
+        console.error("Error resyncing simulation", error)
+      })
+    }
+    lastSequence.current = sequence;
 
-    simulationDataValue.updateProcessesToDelete(processes);
-    simulationDataValue.updateSimulationSpeed(0);
-    simulationDataValue.updateSimulationState("stopped");
-  })
+    switch (simulationEvent.type) {
+      case "Keyframe":
+        simulationDataValue.updateProcesses(simulationEvent.data.processes);
+        simulationDataValue.updateResources(simulationEvent.data.resources);
+        break;
+      case "Delta":
+        applySnapshotDelta(simulationEvent.data);
+        break;
+      case "UnsafeState": {
+        const _processes: string[] = simulationEvent.data.process_ids;
+
+        // Search for the processes with the ids inside the _processes array
+        const processes: Process[] = simulationDataValue.processes.filter((process) => {
+          return _processes.includes(processId(process));
+        });
+
+        console.log("unsafe_state", _processes)
 
-  simulationDataValue.listen<Process[]>("processes", (event) => {
-    simulationDataValue.updateProcesses(event.payload);
+        simulationDataValue.updateProcessesToDelete(processes);
+        simulationDataValue.updateSimulationSpeed(0);
+        simulationDataValue.updateSimulationState("stopped");
+        break;
+      }
+      case "WorkerDied":
+        console.error("Simulation worker died", simulationEvent.data.reason)
+        break;
+    }
   })
 
   return (
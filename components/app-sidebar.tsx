@@ -40,7 +40,7 @@ import {
     DialogTrigger,
 } from "@/components/ui/dialog"
 
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '@/lib/backend';
 // import { listen } from '@tauri-apps/api/event';
 import { Button } from "./ui/button"
 import { Input } from "./ui/input"
@@ -0,0 +1,100 @@
+// Picks where commands go: the Tauri backend inside the app, or the WASM build of
+// the engine when the page is served on its own. Both answer the same commands with
+// the same JSON and emit the same "simulation" events.
+
+import type { EventCallback, EventName, UnlistenFn } from "@tauri-apps/api/event";
+
+type WasmSimulation = {
+  invoke(command: string, args?: unknown): unknown;
+  pump(now: number): number | undefined;
+  events(): unknown[];
+};
+
+type WasmModule = {
+  default: (input?: string) => Promise<unknown>;
+  Simulation: new () => WasmSimulation;
+};
+
+// Output of `wasm-pack build --target web`, see src-tauri/wasm/README.md
+const WASM_MODULE = "/wasm/system_monitor_wasm.js";
+const EVENT_CHANNEL = "simulation";
+
+export function isTauri(): boolean {
+  return typeof window !== "undefined" && "__TAURI_INTERNALS__" in window;
+}
+
+let browser: Promise<WasmSimulation> | null = null;
+const listeners = new Set<EventCallback<unknown>>();
+let timer: ReturnType<typeof setTimeout> | null = null;
+let nextEventId = 0;
+
+function deliver(simulation: WasmSimulation) {
+  for (const payload of simulation.events()) {
+    const event = { event: EVENT_CHANNEL, id: nextEventId++, payload };
+    listeners.forEach((listener) => listener(event));
+  }
+}
+
+// Stands in for the worker thread of the desktop app.
+function schedule(simulation: WasmSimulation, delay: number) {
+  if (timer !== null) {
+    clearTimeout(timer);
+  }
+
+  timer = setTimeout(() => {
+    timer = null;
+    const next = simulation.pump(performance.now());
+    deliver(simulation);
+    if (next !== undefined) {
+      schedule(simulation, next - performance.now());
+    }
+  }, Math.max(0, delay));
+}
+
+function browserSimulation(): Promise<WasmSimulation> {
+  if (browser === null) {
+    browser = (async () => {
+      const wasm: WasmModule = await import(/* webpackIgnore: true */ WASM_MODULE);
+      await wasm.default();
+
+      // The desktop app starts the simulation as soon as the window opens.
+      const simulation = new wasm.Simulation();
+      simulation.invoke("start_simulation");
+      schedule(simulation, 0);
+      return simulation;
+    })();
+  }
+  return browser;
+}
+
+export async function invoke<T>(command: string, args?: Record<string, unknown>): Promise<T> {
+  if (isTauri()) {
+    return (await import("@tauri-apps/api/core")).invoke<T>(command, args);
+  }
+
+  const simulation = await browserSimulation();
+  try {
+    return simulation.invoke(command, args) as T;
+  } finally {
+    // Commands wake the worker up, so let it look at the change right away.
+    deliver(simulation);
+    schedule(simulation, 0);
+  }
+}
+
+export async function listen<T>(event: EventName, handler: EventCallback<T>): Promise<UnlistenFn> {
+  if (isTauri()) {
+    return (await import("@tauri-apps/api/event")).listen<T>(event, handler);
+  }
+
+  if (event !== EVENT_CHANNEL) {
+    return () => { };
+  }
+
+  await browserSimulation();
+  const listener = handler as EventCallback<unknown>;
+  listeners.add(listener);
+  return () => {
+    listeners.delete(listener);
+  };
+}
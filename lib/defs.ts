@@ -54,4 +54,66 @@ export type Resource = {
   free_amount: number;
 };
 
+export type Engine = "Ticked" | "DiscreteEvent";
+
+export type SimulationTime = {
+  tick: number;
+  time: number;
+};
+
+export type Snapshot = {
+  time: SimulationTime;
+  processes: Process[];
+  resources: Resource[];
+};
+
+export type SnapshotDelta = {
+  time: SimulationTime;
+  added_processes: Process[];
+  changed_processes: Process[];
+  removed_processes: string[];
+  added_resources: Resource[];
+  changed_resources: Resource[];
+  removed_resources: string[];
+};
+
+export type SimulationEvent =
+  | { type: "Keyframe"; data: Snapshot }
+  | { type: "Delta"; data: SnapshotDelta }
+  | { type: "StateTransition"; data: { process_id: string; from: string; to: string } }
+  | { type: "Allocation"; data: { process_id: string; slot_id: string; resource_id: string; amount: number } }
+  | { type: "UnsafeState"; data: { process_ids: string[] } }
+  | { type: "WorkerDied"; data: { reason: string } }
+  | { type: "Recovered"; data: { worker_alive: boolean; poisoned: boolean } }
+  | { type: "Started" }
+  | { type: "Stopped" }
+  | { type: "SpeedChanged"; data: { speed: number } }
+  | { type: "TickRate"; data: { ticks_per_second: number } }
+  | { type: "SystemSample"; data: SystemSample }
+  | { type: "MonitorFailed"; data: { reason: string } };
+
+export type SystemSample = {
+  cpu_percent: number;
+  cores: number;
+  memory_total_kib: number;
+  memory_available_kib: number;
+  load_average: [number, number, number];
+};
+
+export type AutosaveEntry = {
+  name: string;
+  saved_at: number;
+};
+
+export type ApiServerInfo = {
+  port: number;
+  token: string;
+};
+
+export type SimulationEventEnvelope = {
+  sequence: number;
+  time: SimulationTime;
+  event: SimulationEvent;
+};
+
 // Define Process
@@ -0,0 +1,266 @@
+//! Python bindings to the simulation engine, for notebooks and scripts.
+//!
+//! Everything the engine returns is handed to Python as plain dicts and lists, in
+//! the same shape the desktop app receives it.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use app_lib::batch::{apply_batch, BatchResult, Mutation};
+use app_lib::discrete::Engine;
+use app_lib::environment::{self, Environment as Episodes};
+use app_lib::events::{EventEnvelope, EventSink};
+use app_lib::generic_process::GenericProcessResourceIntensity;
+use app_lib::scenario::Scenario;
+use app_lib::simulation::{self, AllSimulationTrait, ResourceRemovalPolicy, RunningSimulation};
+
+create_exception!(system_monitor, SimulationError, PyException);
+
+fn error(error: impl ToString) -> PyErr {
+    SimulationError::new_err(error.to_string())
+}
+
+/// Goes through JSON so Python gets exactly what the frontend would.
+fn to_python(py: Python<'_>, value: &impl serde::Serialize) -> PyResult<PyObject> {
+    let text = serde_json::to_string(value).map_err(error)?;
+    Ok(py.import("json")?.call_method1("loads", (text,))?.unbind())
+}
+
+/// Enums are passed by variant name, like `"High"` or `"DiscreteEvent"`.
+fn from_name<T: serde::de::DeserializeOwned>(name: &str) -> PyResult<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).map_err(error)
+}
+
+/// Keeps the events of the current step until they are handed to Python.
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<EventEnvelope>>>);
+
+impl EventSink for Collector {
+    fn send(&self, envelope: EventEnvelope) {
+        if let Ok(mut events) = self.0.lock() {
+            events.push(envelope);
+        }
+    }
+}
+
+/// A simulation driven one step at a time from Python. No worker thread runs, so
+/// nothing changes between calls.
+#[pyclass]
+struct Simulation {
+    simulation: RunningSimulation,
+    events: Collector,
+}
+
+impl Simulation {
+    fn apply(&self, mutations: Vec<Mutation>) -> PyResult<BatchResult> {
+        apply_batch(
+            &self.simulation.resources(),
+            &self.simulation.processes(),
+            mutations,
+        )
+        .map_err(|e| match e {
+            // Every call is a single mutation, its index means nothing to the caller.
+            simulation::SimulationError::BatchFailed { error: inner, .. } => error(inner),
+            e => error(e),
+        })
+    }
+
+    fn take_events(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let events = std::mem::take(&mut *self.events.0.lock().map_err(error)?);
+        to_python(py, &events)
+    }
+}
+
+#[pymethods]
+impl Simulation {
+    #[new]
+    #[pyo3(signature = (seed=None))]
+    fn new(seed: Option<u64>) -> PyResult<Self> {
+        let simulation = RunningSimulation::new();
+        if let Some(seed) = seed {
+            simulation.set_seed(seed).map_err(error)?;
+        }
+
+        Ok(Simulation {
+            simulation,
+            events: Collector::default(),
+        })
+    }
+
+    /// Creates a simulation from a TOML or JSON scenario file.
+    #[staticmethod]
+    #[pyo3(signature = (path, seed=None))]
+    fn from_scenario(path: &str, seed: Option<u64>) -> PyResult<Self> {
+        let scenario = Scenario::read(Path::new(path)).map_err(error)?;
+        let simulation = Simulation::new(seed)?;
+        simulation
+            .simulation
+            .load_scenario(&simulation.events, &scenario)
+            .map_err(error)?;
+        // Loading emits events nobody asked for yet.
+        simulation.events.0.lock().map_err(error)?.clear();
+        Ok(simulation)
+    }
+
+    /// Returns the id of the new resource.
+    #[pyo3(signature = (name, total_amount, blocking=false))]
+    fn add_resource(&self, name: String, total_amount: u64, blocking: bool) -> PyResult<String> {
+        let result = self.apply(vec![Mutation::CreateResource {
+            key: "resource".to_string(),
+            name,
+            total_amount,
+            blocking,
+        }])?;
+        Ok(result.created("resource").cloned().unwrap_or_default())
+    }
+
+    /// Returns the id of the new process. The intensity is one of `"None"`, `"Low"`,
+    /// `"Medium"`, `"High"` or `"Extreme"`.
+    #[pyo3(signature = (name, resource_intensity="Medium"))]
+    fn add_process(&self, name: String, resource_intensity: &str) -> PyResult<String> {
+        let resource_intensity: GenericProcessResourceIntensity = from_name(resource_intensity)?;
+        let result = self.apply(vec![Mutation::CreateProcess {
+            key: "process".to_string(),
+            name,
+            resource_intensity,
+        }])?;
+        Ok(result.created("process").cloned().unwrap_or_default())
+    }
+
+    fn add_slot(&self, process_id: String, resource_id: String, amount: u64) -> PyResult<()> {
+        self.apply(vec![Mutation::AddSlot {
+            process: process_id,
+            resource: resource_id,
+            amount,
+        }])?;
+        Ok(())
+    }
+
+    fn remove_process(&self, process_id: String) -> PyResult<()> {
+        self.apply(vec![Mutation::RemoveProcess {
+            process: process_id,
+        }])?;
+        Ok(())
+    }
+
+    /// `policy` is `"Refuse"`, `"ReleaseSlots"` or `"DeleteProcesses"`.
+    #[pyo3(signature = (resource_id, policy="Refuse"))]
+    fn remove_resource(&self, resource_id: String, policy: &str) -> PyResult<()> {
+        let policy: ResourceRemovalPolicy = from_name(policy)?;
+        self.apply(vec![Mutation::RemoveResource {
+            resource: resource_id,
+            policy,
+        }])?;
+        Ok(())
+    }
+
+    /// `"Ticked"` or `"DiscreteEvent"`.
+    fn set_engine(&self, engine: &str) -> PyResult<()> {
+        let engine: Engine = from_name(engine)?;
+        self.simulation.set_engine(engine).map_err(error)
+    }
+
+    fn set_seed(&self, seed: u64) -> PyResult<()> {
+        self.simulation.set_seed(seed).map_err(error)
+    }
+
+    /// Runs one tick and returns the events it produced. An `UnsafeState` event
+    /// means the step left the simulation in an unsafe state.
+    fn step(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.simulation.step(&self.events).map_err(error)?;
+        self.take_events(py)
+    }
+
+    /// Whether the current requests still let every process finish.
+    fn safe_to_continue(&self) -> PyResult<bool> {
+        self.simulation.is_safe().map_err(error)
+    }
+
+    /// Time, processes and resources as one dict.
+    fn snapshot(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.simulation.resync().map_err(error)?)
+    }
+
+    fn processes(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let processes = self.simulation.processes();
+        let processes = processes.lock().map_err(error)?;
+        to_python(py, &*processes)
+    }
+
+    fn resources(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let resources = self.simulation.resources();
+        let resources = resources.lock().map_err(error)?;
+        to_python(py, &*resources)
+    }
+}
+
+/// Reinforcement-learning episodes over a scenario, in the style of Gymnasium.
+///
+/// `reset` returns the observation dict and `step` takes one bool per process and
+/// returns `(observation, reward, terminated, truncated, info)`.
+#[pyclass]
+struct Environment {
+    environment: Episodes,
+}
+
+#[pymethods]
+impl Environment {
+    #[staticmethod]
+    #[pyo3(signature = (path, max_steps=environment::DEFAULT_MAX_STEPS))]
+    fn from_scenario(path: &str, max_steps: u64) -> PyResult<Self> {
+        let scenario = Scenario::read(Path::new(path)).map_err(error)?;
+        Ok(Environment {
+            environment: Episodes::from_scenario(&scenario, max_steps).map_err(error)?,
+        })
+    }
+
+    /// Process names, in the order of the observation rows.
+    #[getter]
+    fn processes(&self) -> Vec<String> {
+        self.environment.processes()
+    }
+
+    /// Resource names, in the order of the observation columns.
+    #[getter]
+    fn resources(&self) -> Vec<String> {
+        self.environment.resources()
+    }
+
+    #[pyo3(signature = (seed=0))]
+    fn reset(&mut self, py: Python<'_>, seed: u64) -> PyResult<PyObject> {
+        to_python(py, &self.environment.reset(seed))
+    }
+
+    fn step(
+        &mut self,
+        py: Python<'_>,
+        action: Vec<bool>,
+    ) -> PyResult<(PyObject, f64, bool, bool, PyObject)> {
+        let result = self.environment.step(&action).map_err(error)?;
+        let info = serde_json::json!({ "outcome": result.outcome });
+        Ok((
+            to_python(py, &result.observation)?,
+            result.reward,
+            result.terminated,
+            result.truncated,
+            to_python(py, &info)?,
+        ))
+    }
+
+    /// What the `safe_to_continue` allocator would do, to compare against.
+    fn baseline_action(&self) -> Vec<bool> {
+        self.environment.baseline_action()
+    }
+}
+
+#[pymodule]
+fn system_monitor(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Simulation>()?;
+    m.add_class::<Environment>()?;
+    m.add("SimulationError", m.py().get_type::<SimulationError>())?;
+    Ok(())
+}
@@ -0,0 +1,742 @@
+use std::fmt;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use nanoid::nanoid;
+use serde_json::{json, Value};
+#[cfg(feature = "desktop")]
+use tauri::{Listener, Manager};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::batch::{apply_batch, BatchResult, Mutation};
+use crate::discrete::Engine;
+use crate::events::{EventEnvelope, EventSink, SimulationEvent};
+use crate::generic_process::{GenericProcessResourceIntensity, ProcessStates};
+use crate::generic_resource::GenericResource;
+use crate::simulation::{
+    lock_state, AllSimulationTrait, ResourceRemovalPolicy, RunningSimulation, SimulationError,
+};
+#[cfg(feature = "desktop")]
+use crate::{events::EVENT_CHANNEL, TauriSim};
+
+/// Events waiting to be sent to one WebSocket client.
+const QUEUE_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiError {
+    Bind(String),
+    AlreadyRunning,
+    NotRunning,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Bind(error) => write!(f, "Could not start the API server: {}", error),
+            ApiError::AlreadyRunning => write!(f, "The API server is already running"),
+            ApiError::NotRunning => write!(f, "The API server is not running"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl serde::Serialize for ApiError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Fans events out to every connected WebSocket client. Slow clients miss events
+/// instead of holding up the simulation; the envelope sequence shows the gap.
+#[derive(Clone, Default)]
+pub struct Hub(Arc<Mutex<Vec<SyncSender<String>>>>);
+
+impl Hub {
+    pub fn broadcast(&self, message: &str) {
+        let Ok(mut clients) = self.0.lock() else {
+            return;
+        };
+        clients.retain(|client| match client.try_send(message.to_string()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+
+    fn subscribe(&self) -> Option<Receiver<String>> {
+        let (tx, rx) = sync_channel(QUEUE_SIZE);
+        self.0.lock().ok()?.push(tx);
+        Some(rx)
+    }
+}
+
+impl EventSink for Hub {
+    fn send(&self, envelope: EventEnvelope) {
+        if let Ok(message) = serde_json::to_string(&envelope) {
+            self.broadcast(&message);
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct ApiServerInfo {
+    port: u16,
+    token: String,
+}
+
+/// HTTP server bound to localhost that exposes the simulation as REST resources and
+/// streams its events over a WebSocket at `/api/events`.
+///
+/// Every request needs the token, as `Authorization: Bearer <token>` or, for
+/// WebSocket clients that cannot set headers, as `?token=<token>`.
+pub struct ApiServer {
+    server: Arc<Server>,
+    info: ApiServerInfo,
+}
+
+impl ApiServer {
+    /// Binds `127.0.0.1:port`, port 0 picks a free one. Without a token a random one
+    /// is generated. `sink` receives the events caused by requests, `hub` is what
+    /// WebSocket clients listen to.
+    pub fn start<S: EventSink>(
+        simulation: RunningSimulation,
+        sink: S,
+        hub: Hub,
+        port: u16,
+        token: Option<String>,
+    ) -> Result<Self, ApiError> {
+        let server =
+            Server::http(("127.0.0.1", port)).map_err(|e| ApiError::Bind(e.to_string()))?;
+        let server = Arc::new(server);
+        let port = server
+            .server_addr()
+            .to_ip()
+            .map_or(port, |address| address.port());
+        let token = token.unwrap_or_else(|| nanoid!(32));
+
+        let api = Api {
+            simulation,
+            sink,
+            hub,
+            token: token.clone(),
+        };
+        let requests = Arc::clone(&server);
+        thread::spawn(move || {
+            for request in requests.incoming_requests() {
+                api.handle(request);
+            }
+        });
+
+        Ok(ApiServer {
+            server,
+            info: ApiServerInfo { port, token },
+        })
+    }
+
+    pub fn info(&self) -> &ApiServerInfo {
+        &self.info
+    }
+
+    pub fn port(&self) -> u16 {
+        self.info.port
+    }
+
+    pub fn token(&self) -> &str {
+        &self.info.token
+    }
+
+    /// Stops accepting requests. Open WebSocket streams end with their clients.
+    pub fn stop(&self) {
+        self.server.unblock();
+    }
+}
+
+type Reply = Result<(u16, Value), (u16, String)>;
+
+fn failed(error: SimulationError) -> (u16, String) {
+    fn status(error: &SimulationError) -> u16 {
+        match error {
+            SimulationError::ProcessNotFound
+            | SimulationError::ResourceNotFound
+            | SimulationError::SlotNotFound => 404,
+            SimulationError::ResourceInUse(_) => 409,
+            SimulationError::Invalid(_)
+            | SimulationError::Scenario(_)
+            | SimulationError::SaveState(_) => 400,
+            SimulationError::BatchFailed { error, .. } => status(error),
+            _ => 500,
+        }
+    }
+
+    // A single mutation failing is the request failing, not "mutation 0".
+    let error = match error {
+        SimulationError::BatchFailed { error, .. } => *error,
+        error => error,
+    };
+    (status(&error), error.to_string())
+}
+
+/// Compares every byte, so how long the check takes does not tell how much of a
+/// guessed token was right.
+fn same_token(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Decodes `%XX` escapes and `+` in a query value. Malformed escapes are kept as is.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn body<T: serde::de::DeserializeOwned>(body: &str) -> Result<T, (u16, String)> {
+    serde_json::from_str(body).map_err(|e| (400, format!("Invalid body: {}", e)))
+}
+
+fn to_json(value: impl serde::Serialize) -> Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
+
+#[derive(serde::Deserialize)]
+struct NewProcess {
+    name: String,
+    resource_intensity: GenericProcessResourceIntensity,
+}
+
+#[derive(serde::Deserialize)]
+struct NewSlot {
+    resource_id: String,
+    amount: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct NewResource {
+    name: String,
+    total_amount: u64,
+    #[serde(default)]
+    blocking: bool,
+}
+
+#[derive(serde::Deserialize)]
+struct ResourceUpdate {
+    total_amount: u64,
+}
+
+/// Only the fields present are changed.
+#[derive(serde::Deserialize)]
+struct SettingsUpdate {
+    speed: Option<f64>,
+    time_step: Option<f64>,
+    engine: Option<Engine>,
+    publish_rate: Option<u64>,
+    unbounded: Option<bool>,
+}
+
+struct Api<S: EventSink> {
+    simulation: RunningSimulation,
+    sink: S,
+    hub: Hub,
+    token: String,
+}
+
+impl<S: EventSink> Api<S> {
+    fn handle(&self, mut request: Request) {
+        let (path, query) = match request.url().split_once('?') {
+            Some((path, query)) => (path.to_string(), query.to_string()),
+            None => (request.url().to_string(), String::new()),
+        };
+        let query = |name: &str| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| percent_decode(value))
+        };
+
+        let bearer = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Authorization"))
+            .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+            .map(str::to_string);
+        let token = bearer.or_else(|| query("token")).unwrap_or_default();
+        if !same_token(&token, &self.token) {
+            respond(request, Err((401, "Missing or wrong token".to_string())));
+            return;
+        }
+
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        if request.method() == &Method::Get && segments == ["api", "events"] {
+            self.stream_events(request);
+            return;
+        }
+
+        let mut content = String::new();
+        if let Err(e) = request.as_reader().read_to_string(&mut content) {
+            respond(request, Err((400, e.to_string())));
+            return;
+        }
+
+        let reply = self.route(request.method(), &segments, &content, query("policy"));
+        respond(request, reply);
+    }
+
+    fn route(
+        &self,
+        method: &Method,
+        segments: &[&str],
+        content: &str,
+        policy: Option<String>,
+    ) -> Reply {
+        match (method, segments) {
+            (Method::Get, ["api", "processes"]) => Ok((200, to_json(self.processes()?))),
+            (Method::Post, ["api", "processes"]) => {
+                let new: NewProcess = body(content)?;
+                let result = self.apply(vec![Mutation::CreateProcess {
+                    key: "process".to_string(),
+                    name: new.name,
+                    resource_intensity: new.resource_intensity,
+                }])?;
+                Ok((
+                    201,
+                    to_json(self.process(result.created("process").map(String::as_str))?),
+                ))
+            }
+            (Method::Get, ["api", "processes", id]) => Ok((200, to_json(self.process(Some(*id))?))),
+            (Method::Delete, ["api", "processes", id]) => {
+                self.apply(vec![Mutation::RemoveProcess {
+                    process: id.to_string(),
+                }])?;
+                Ok((204, Value::Null))
+            }
+            (Method::Get, ["api", "processes", id, "slots"]) => {
+                let process = self.process(Some(*id))?;
+                Ok((200, to_json(process.process().resource_slot())))
+            }
+            (Method::Post, ["api", "processes", id, "slots"]) => {
+                let new: NewSlot = body(content)?;
+                self.apply(vec![Mutation::AddSlot {
+                    process: id.to_string(),
+                    resource: new.resource_id,
+                    amount: new.amount,
+                }])?;
+                Ok((201, to_json(self.process(Some(*id))?)))
+            }
+            (Method::Delete, ["api", "processes", id, "slots", resource_id]) => {
+                self.apply(vec![Mutation::RemoveSlot {
+                    process: id.to_string(),
+                    resource: resource_id.to_string(),
+                }])?;
+                Ok((204, Value::Null))
+            }
+            (Method::Get, ["api", "resources"]) => {
+                let resources = self.simulation.resources();
+                let resources = lock_state(&resources).map_err(failed)?;
+                Ok((200, to_json(&*resources)))
+            }
+            (Method::Post, ["api", "resources"]) => {
+                let new: NewResource = body(content)?;
+                let result = self.apply(vec![Mutation::CreateResource {
+                    key: "resource".to_string(),
+                    name: new.name,
+                    total_amount: new.total_amount,
+                    blocking: new.blocking,
+                }])?;
+                Ok((
+                    201,
+                    to_json(self.resource(result.created("resource").map(String::as_str))?),
+                ))
+            }
+            (Method::Get, ["api", "resources", id]) => {
+                Ok((200, to_json(self.resource(Some(*id))?)))
+            }
+            (Method::Patch, ["api", "resources", id]) => {
+                let update: ResourceUpdate = body(content)?;
+                self.apply(vec![Mutation::SetResourceTotalAmount {
+                    resource: id.to_string(),
+                    total_amount: update.total_amount,
+                }])?;
+                Ok((200, to_json(self.resource(Some(*id))?)))
+            }
+            (Method::Delete, ["api", "resources", id]) => {
+                let policy: ResourceRemovalPolicy = match policy {
+                    Some(policy) => serde_json::from_value(Value::String(policy))
+                        .map_err(|e| (400, format!("Invalid policy: {}", e)))?,
+                    None => ResourceRemovalPolicy::default(),
+                };
+                let removal = self
+                    .simulation
+                    .clone()
+                    .remove_resource_by_id(id.to_string(), policy)
+                    .map_err(failed)?;
+                self.simulation.wake();
+                Ok((200, to_json(removal)))
+            }
+            (Method::Get, ["api", "settings"]) => Ok((200, self.settings()?)),
+            (Method::Patch, ["api", "settings"]) => {
+                let update: SettingsUpdate = body(content)?;
+                self.update_settings(update).map_err(failed)?;
+                Ok((200, self.settings()?))
+            }
+            (Method::Get, ["api", "snapshot"]) => {
+                Ok((200, to_json(self.simulation.resync().map_err(failed)?)))
+            }
+            (Method::Post, ["api", "start"]) => {
+                self.simulation.start(&self.sink).map_err(failed)?;
+                Ok((204, Value::Null))
+            }
+            (Method::Post, ["api", "stop"]) => {
+                self.simulation.stop(&self.sink).map_err(failed)?;
+                Ok((204, Value::Null))
+            }
+            _ => Err((404, "Not found".to_string())),
+        }
+    }
+
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<BatchResult, (u16, String)> {
+        let result = apply_batch(
+            &self.simulation.resources(),
+            &self.simulation.processes(),
+            mutations,
+        )
+        .map_err(failed)?;
+        self.simulation.wake();
+        Ok(result)
+    }
+
+    fn processes(&self) -> Result<Value, (u16, String)> {
+        let processes = self.simulation.processes();
+        let processes = lock_state(&processes).map_err(failed)?;
+        Ok(to_json(&*processes))
+    }
+
+    fn process(&self, id: Option<&str>) -> Result<ProcessStates, (u16, String)> {
+        self.simulation
+            .get_process_by_id(id.unwrap_or_default().to_string())
+            .map_err(failed)?
+            .ok_or(failed(SimulationError::ProcessNotFound))
+    }
+
+    fn resource(&self, id: Option<&str>) -> Result<GenericResource, (u16, String)> {
+        self.simulation
+            .get_resource_by_id(id.unwrap_or_default().to_string())
+            .map_err(failed)?
+            .ok_or(failed(SimulationError::ResourceNotFound))
+    }
+
+    fn settings(&self) -> Result<Value, (u16, String)> {
+        let scenario = self.simulation.scenario().map_err(failed)?;
+        Ok(to_json(scenario.settings()))
+    }
+
+    fn update_settings(&self, update: SettingsUpdate) -> Result<(), SimulationError> {
+        if let Some(speed) = update.speed {
+            self.simulation.clone().set_simulation_speed(speed)?;
+            self.simulation.wake();
+            self.simulation
+                .events()
+                .emit(&self.sink, SimulationEvent::SpeedChanged { speed });
+        }
+        if let Some(time_step) = update.time_step {
+            self.simulation.set_time_step(time_step)?;
+        }
+        if let Some(engine) = update.engine {
+            self.simulation.set_engine(engine)?;
+        }
+        if let Some(publish_rate) = update.publish_rate {
+            self.simulation.set_publish_rate(publish_rate)?;
+        }
+        if let Some(unbounded) = update.unbounded {
+            self.simulation.set_unbounded(unbounded)?;
+        }
+        Ok(())
+    }
+
+    /// Upgrades the request to a WebSocket that receives every event as JSON text.
+    fn stream_events(&self, request: Request) {
+        let key = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Sec-WebSocket-Key"))
+            .map(|h| h.value.as_str().to_string());
+        let (Some(key), Some(events)) = (key, self.hub.subscribe()) else {
+            respond(
+                request,
+                Err((400, "Expected a WebSocket upgrade".to_string())),
+            );
+            return;
+        };
+
+        let accept = derive_accept_key(key.as_bytes());
+        let response = Response::empty(StatusCode(101)).with_header(
+            Header::from_bytes(&b"Sec-WebSocket-Accept"[..], accept.as_bytes())
+                .expect("accept key is ascii"),
+        );
+        let stream = request.upgrade("websocket", response);
+
+        thread::spawn(move || {
+            let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+            for event in events {
+                if socket.send(Message::Text(event)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+fn respond(request: Request, reply: Reply) {
+    let (status, value) = match reply {
+        Ok(reply) => reply,
+        Err((status, error)) => (status, json!({ "error": error })),
+    };
+
+    let response = if status == 204 {
+        Response::from_string("").with_status_code(status)
+    } else {
+        Response::from_string(value.to_string())
+            .with_status_code(status)
+            .with_header(
+                Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                    .expect("static header"),
+            )
+    };
+
+    if let Err(e) = request.respond(response) {
+        println!("Error: {}", e);
+    }
+}
+
+/// The server started from the window, if any.
+#[cfg(feature = "desktop")]
+#[derive(Default)]
+pub struct ApiServerState(Option<(ApiServer, tauri::EventId)>);
+
+/// Starts the API server next to the window. Events reach WebSocket clients by
+/// listening to the same channel the webview gets them from.
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn api_server_start(
+    app_handle: tauri::AppHandle,
+    port: Option<u16>,
+    token: Option<String>,
+) -> Result<ApiServerInfo, SimulationError> {
+    let servers = app_handle.state::<Mutex<ApiServerState>>();
+    let mut servers = lock_state(&servers)?;
+    if servers.0.is_some() {
+        return Err(ApiError::AlreadyRunning.into());
+    }
+
+    let simulation = {
+        let state = app_handle.state::<Mutex<TauriSim>>();
+        let sim = lock_state(&state)?;
+        sim.0.clone()
+    };
+
+    let hub = Hub::default();
+    let server = ApiServer::start(
+        simulation,
+        app_handle.clone(),
+        hub.clone(),
+        port.unwrap_or(0),
+        token,
+    )?;
+    let listener =
+        app_handle.listen_any(EVENT_CHANNEL, move |event| hub.broadcast(event.payload()));
+
+    let info = server.info().clone();
+    servers.0 = Some((server, listener));
+    Ok(info)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn api_server_stop(app_handle: tauri::AppHandle) -> Result<(), SimulationError> {
+    let servers = app_handle.state::<Mutex<ApiServerState>>();
+    let mut servers = lock_state(&servers)?;
+    let (server, listener) = servers.0.take().ok_or(ApiError::NotRunning)?;
+
+    server.stop();
+    app_handle.unlisten(listener);
+    Ok(())
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn api_server_status(
+    app_handle: tauri::AppHandle,
+) -> Result<Option<ApiServerInfo>, SimulationError> {
+    let servers = app_handle.state::<Mutex<ApiServerState>>();
+    let servers = lock_state(&servers)?;
+    Ok(servers.0.as_ref().map(|(server, _)| server.info().clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Recorder;
+    use crate::simulation::tests::simulation;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    const TOKEN: &str = "secret token/1";
+
+    fn server() -> ApiServer {
+        ApiServer::start(
+            simulation(),
+            Recorder::default(),
+            Hub::default(),
+            0,
+            Some(TOKEN.to_string()),
+        )
+        .unwrap()
+    }
+
+    /// Sends one request and returns the status and the decoded JSON body, if any.
+    fn send(
+        server: &ApiServer,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+        body: &str,
+    ) -> (u16, Value) {
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            authorization,
+            body.len(),
+            body
+        )
+        .unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, content) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(content).unwrap_or(Value::Null))
+    }
+
+    fn memory_id(server: &ApiServer) -> String {
+        let (_, resources) = send(server, "GET", "/api/resources", Some(TOKEN), "");
+        resources[0]["id"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let server = server();
+
+        assert_eq!(send(&server, "GET", "/api/processes", None, "").0, 401);
+        assert_eq!(
+            send(&server, "GET", "/api/processes", Some("secret"), "").0,
+            401
+        );
+        assert_eq!(
+            send(&server, "GET", "/api/processes", Some(TOKEN), "").0,
+            200
+        );
+        // WebSocket clients pass it in the query, escaped like any other value.
+        assert_eq!(
+            send(
+                &server,
+                "GET",
+                "/api/processes?token=secret+token%2F1",
+                None,
+                ""
+            )
+            .0,
+            200
+        );
+        server.stop();
+    }
+
+    #[test]
+    fn errors_map_to_statuses() {
+        let server = server();
+
+        let (status, body) = send(&server, "GET", "/api/processes/missing", Some(TOKEN), "");
+        assert_eq!(status, 404);
+        assert_eq!(body["error"], SimulationError::ProcessNotFound.to_string());
+
+        // Every process holds a slot for the only resource.
+        let path = format!("/api/resources/{}", memory_id(&server));
+        assert_eq!(send(&server, "DELETE", &path, Some(TOKEN), "").0, 409);
+
+        let blank = r#"{"name": " ", "resource_intensity": "Low"}"#;
+        assert_eq!(
+            send(&server, "POST", "/api/processes", Some(TOKEN), blank).0,
+            400
+        );
+        assert_eq!(
+            send(&server, "POST", "/api/processes", Some(TOKEN), "{").0,
+            400
+        );
+        server.stop();
+    }
+
+    #[test]
+    fn created_process_can_be_read_back() {
+        let server = server();
+
+        let new = r#"{"name": "Compiler", "resource_intensity": "Extreme"}"#;
+        let (status, created) = send(&server, "POST", "/api/processes", Some(TOKEN), new);
+        assert_eq!(status, 201);
+        assert_eq!(created["Ready"]["name"], "Compiler");
+
+        let id = created["Ready"]["id"].as_str().unwrap();
+        let path = format!("/api/processes/{}", id);
+        let (status, read) = send(&server, "GET", &path, Some(TOKEN), "");
+        assert_eq!(status, 200);
+        assert_eq!(read, created);
+
+        let (_, processes) = send(&server, "GET", "/api/processes", Some(TOKEN), "");
+        assert_eq!(processes.as_array().unwrap().len(), 4);
+        server.stop();
+    }
+
+    #[test]
+    fn query_values_are_decoded() {
+        assert_eq!(percent_decode("a%20b+c%2f"), "a b c/");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%+1"), "%zz% 1");
+    }
+}
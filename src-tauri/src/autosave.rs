@@ -0,0 +1,349 @@
+use std::path::{Path, PathBuf};
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+#[cfg(feature = "desktop")]
+use std::thread;
+#[cfg(feature = "desktop")]
+use std::time::{Duration, Instant};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "desktop")]
+use tauri::{AppHandle, Manager};
+
+use crate::save_state::{SaveState, SaveStateError};
+#[cfg(feature = "desktop")]
+use crate::simulation::lock_state;
+use crate::simulation::{RunningSimulation, SimulationError};
+#[cfg(feature = "desktop")]
+use crate::snapshot::Snapshot;
+#[cfg(feature = "desktop")]
+use crate::TauriSim;
+
+#[cfg(feature = "desktop")]
+const AUTOSAVE_DIR: &str = "autosave";
+const SETTINGS_FILE: &str = "settings.json";
+const FILE_PREFIX: &str = "autosave-";
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+#[serde(default)]
+pub struct AutosaveSettings {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Autosaves kept on disk, the oldest ones are deleted first.
+    pub retention: usize,
+}
+
+impl Default for AutosaveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_secs: 30,
+            retention: 5,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct AutosaveEntry {
+    name: String,
+    /// Milliseconds since the Unix epoch.
+    saved_at: u64,
+}
+
+/// Periodic saves of the live state to the app data directory, so a closed or
+/// crashed session can be restored on the next start.
+pub struct Autosave {
+    dir: PathBuf,
+    settings: AutosaveSettings,
+    /// Contents of the last autosave, to skip writing the same state again.
+    last: Option<String>,
+}
+
+impl Autosave {
+    pub fn new(dir: PathBuf) -> Self {
+        let settings = std::fs::read_to_string(dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            dir,
+            settings,
+            last: None,
+        }
+    }
+
+    pub fn settings(&self) -> &AutosaveSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: AutosaveSettings) -> Result<(), SaveStateError> {
+        self.settings = AutosaveSettings {
+            interval_secs: settings.interval_secs.max(1),
+            retention: settings.retention.max(1),
+            ..settings
+        };
+
+        let contents = serde_json::to_string_pretty(&self.settings)
+            .map_err(|e| SaveStateError::Parse(e.to_string()))?;
+        std::fs::write(self.dir.join(SETTINGS_FILE), contents)
+            .map_err(|e| SaveStateError::Io(e.to_string()))
+    }
+
+    /// Autosaves on disk, oldest first.
+    pub fn entries(&self) -> Result<Vec<AutosaveEntry>, SaveStateError> {
+        let mut entries: Vec<AutosaveEntry> = std::fs::read_dir(&self.dir)
+            .map_err(|e| SaveStateError::Io(e.to_string()))?
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let saved_at = name
+                    .strip_prefix(FILE_PREFIX)?
+                    .strip_suffix(".json")?
+                    .parse()
+                    .ok()?;
+                Some(AutosaveEntry { name, saved_at })
+            })
+            .collect();
+
+        entries.sort_by_key(|entry| entry.saved_at);
+        Ok(entries)
+    }
+
+    pub fn save(&mut self, state: &SaveState) -> Result<(), SaveStateError> {
+        let contents =
+            serde_json::to_string(state).map_err(|e| SaveStateError::Parse(e.to_string()))?;
+        if self.last.as_ref() == Some(&contents) {
+            return Ok(());
+        }
+
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self.dir.join(format!("{}{}.json", FILE_PREFIX, saved_at));
+
+        // Write next to it and rename, so a crash mid-write never leaves a torn file
+        // as the newest autosave.
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, &contents).map_err(|e| SaveStateError::Io(e.to_string()))?;
+        std::fs::rename(&temporary, &path).map_err(|e| SaveStateError::Io(e.to_string()))?;
+        self.last = Some(contents);
+
+        let entries = self.entries()?;
+        let excess = entries.len().saturating_sub(self.settings.retention);
+        for entry in &entries[..excess] {
+            let _ = std::fs::remove_file(self.dir.join(&entry.name));
+        }
+
+        Ok(())
+    }
+
+    /// Saves the simulation, unless it is still empty.
+    pub fn save_simulation(
+        &mut self,
+        simulation: &RunningSimulation,
+    ) -> Result<(), SimulationError> {
+        let saved = simulation.save_state()?;
+
+        // An empty simulation is what every session starts with, saving it would
+        // only push the previous session out of the retention window.
+        if saved.processes.is_empty() && saved.resources.is_empty() {
+            return Ok(());
+        }
+
+        Ok(self.save(&saved)?)
+    }
+
+    pub fn path_of(&self, name: Option<String>) -> Result<PathBuf, SaveStateError> {
+        let name = match name {
+            Some(name) => name,
+            None => {
+                self.entries()?
+                    .pop()
+                    .ok_or(SaveStateError::NoAutosave)?
+                    .name
+            }
+        };
+
+        // Only plain file names, so the frontend cannot point outside the directory.
+        match Path::new(&name).file_name() {
+            Some(file_name) if file_name == name.as_str() => Ok(self.dir.join(name)),
+            _ => Err(SaveStateError::NoAutosave),
+        }
+    }
+}
+
+/// Creates the autosave directory and starts the thread that writes to it.
+#[cfg(feature = "desktop")]
+pub fn start_autosave(app: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = app.path().app_data_dir()?.join(AUTOSAVE_DIR);
+    std::fs::create_dir_all(&dir)?;
+    app.manage(Mutex::new(Autosave::new(dir)));
+
+    thread::spawn(move || {
+        let mut last_save = Instant::now();
+
+        loop {
+            thread::sleep(Duration::from_secs(1));
+
+            let autosave = app.state::<Mutex<Autosave>>();
+            let Ok(mut autosave) = autosave.lock() else {
+                return;
+            };
+            let settings = autosave.settings.clone();
+            if !settings.enabled
+                || last_save.elapsed() < Duration::from_secs(settings.interval_secs)
+            {
+                continue;
+            }
+            last_save = Instant::now();
+
+            let state = app.state::<Mutex<TauriSim>>();
+            let simulation = match lock_state(&state) {
+                Ok(sim) => sim.0.clone(),
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = autosave.save_simulation(&simulation) {
+                println!("Error: {}", e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn autosave_settings(
+    app_handle: tauri::AppHandle,
+) -> Result<AutosaveSettings, SimulationError> {
+    let autosave = app_handle.state::<Mutex<Autosave>>();
+    let autosave = lock_state(&autosave)?;
+    Ok(autosave.settings.clone())
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn autosave_set_settings(
+    app_handle: tauri::AppHandle,
+    settings: AutosaveSettings,
+) -> Result<(), SimulationError> {
+    let autosave = app_handle.state::<Mutex<Autosave>>();
+    let mut autosave = lock_state(&autosave)?;
+    Ok(autosave.set_settings(settings)?)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn autosave_list(app_handle: tauri::AppHandle) -> Result<Vec<AutosaveEntry>, SimulationError> {
+    let autosave = app_handle.state::<Mutex<Autosave>>();
+    let autosave = lock_state(&autosave)?;
+    Ok(autosave.entries()?)
+}
+
+/// Restores the autosave called `name`, or the newest one.
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn autosave_restore(
+    app_handle: tauri::AppHandle,
+    name: Option<String>,
+) -> Result<Snapshot, SimulationError> {
+    let path = {
+        let autosave = app_handle.state::<Mutex<Autosave>>();
+        let autosave = lock_state(&autosave)?;
+        autosave.path_of(name)?
+    };
+    let saved = SaveState::read(&path)?;
+
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.restore_state(&app_handle, saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Recorder;
+    use crate::simulation::tests::{simulation, temp_dir, within_timeout};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn autosave_while_the_worker_ticks() {
+        let dir = temp_dir("autosave");
+        let simulation = simulation();
+        let sink = Recorder::default();
+        simulation.set_unbounded(true).unwrap();
+        simulation.start(&sink).unwrap();
+
+        let mut autosave = Autosave::new(dir);
+        within_timeout(move || {
+            let until = Instant::now() + Duration::from_secs(2);
+            while Instant::now() < until {
+                autosave.save_simulation(&simulation).unwrap();
+            }
+
+            let entries = autosave.entries().unwrap();
+            assert!(!entries.is_empty());
+            assert!(entries.len() <= autosave.settings.retention);
+        });
+    }
+
+    #[test]
+    fn empty_simulations_are_not_saved() {
+        let mut autosave = Autosave::new(temp_dir("autosave-empty"));
+        autosave.save_simulation(&RunningSimulation::new()).unwrap();
+        assert!(autosave.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_the_newest_autosaves_are_kept() {
+        let mut autosave = Autosave::new(temp_dir("autosave-retention"));
+        autosave
+            .set_settings(AutosaveSettings {
+                retention: 2,
+                ..AutosaveSettings::default()
+            })
+            .unwrap();
+
+        let simulation = simulation();
+        for seed in 0..4 {
+            simulation.set_seed(seed).unwrap();
+            autosave.save_simulation(&simulation).unwrap();
+            // File names are milliseconds, two saves must not share one.
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let entries = autosave.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        // The same state again is not written a second time.
+        autosave.save_simulation(&simulation).unwrap();
+        assert_eq!(autosave.entries().unwrap(), entries);
+
+        // Settings survive a restart.
+        let dir = autosave.dir.clone();
+        assert_eq!(Autosave::new(dir).settings().retention, 2);
+    }
+
+    #[test]
+    fn path_of_stays_in_the_directory() {
+        let dir = temp_dir("autosave-path");
+        let mut autosave = Autosave::new(dir.clone());
+        assert_eq!(autosave.path_of(None), Err(SaveStateError::NoAutosave));
+
+        autosave.save_simulation(&simulation()).unwrap();
+        let newest = autosave.entries().unwrap().pop().unwrap();
+        assert_eq!(autosave.path_of(None), Ok(dir.join(&newest.name)));
+        assert_eq!(
+            autosave.path_of(Some(newest.name.clone())),
+            Ok(dir.join(&newest.name))
+        );
+        assert_eq!(
+            autosave.path_of(Some(format!("../{}", newest.name))),
+            Err(SaveStateError::NoAutosave)
+        );
+    }
+}
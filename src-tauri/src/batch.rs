@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+use crate::generic_process::{
+    AllProcessTraits, GenericProcessResourceIntensity, Process, ProcessStates,
+};
+use crate::generic_resource::GenericResource;
+use crate::simulation::{lock_state, remove_resource_from, ResourceRemovalPolicy, SimulationError};
+use crate::validation::{validate_name, validate_slot, validate_total_amount};
+#[cfg(feature = "desktop")]
+use crate::{simulation::AllSimulationTrait, TauriSim};
+
+/// A single change inside a batch. Processes and resources are referenced either by
+/// their id or by the `key` given to them earlier in the same batch.
+#[derive(Clone, serde::Deserialize, Debug)]
+pub enum Mutation {
+    CreateResource {
+        key: String,
+        name: String,
+        total_amount: u64,
+        blocking: bool,
+    },
+    CreateProcess {
+        key: String,
+        name: String,
+        resource_intensity: GenericProcessResourceIntensity,
+    },
+    AddSlot {
+        process: String,
+        resource: String,
+        amount: u64,
+    },
+    RemoveSlot {
+        process: String,
+        resource: String,
+    },
+    RemoveProcess {
+        process: String,
+    },
+    RemoveResource {
+        resource: String,
+        #[serde(default)]
+        policy: ResourceRemovalPolicy,
+    },
+    SetResourceTotalAmount {
+        resource: String,
+        total_amount: u64,
+    },
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct BatchResult {
+    processes: Vec<ProcessStates>,
+    resources: Vec<GenericResource>,
+    /// Id assigned to every `key` created by the batch.
+    created: HashMap<String, String>,
+}
+
+impl BatchResult {
+    /// Id assigned to the process or resource created with `key`.
+    pub fn created(&self, key: &str) -> Option<&String> {
+        self.created.get(key)
+    }
+}
+
+struct Batch {
+    processes: Vec<ProcessStates>,
+    resources: Vec<GenericResource>,
+    created: HashMap<String, String>,
+}
+
+impl Batch {
+    fn resolve(&self, reference: &str) -> String {
+        match self.created.get(reference) {
+            Some(id) => id.clone(),
+            None => reference.to_string(),
+        }
+    }
+
+    fn process_mut(&mut self, reference: &str) -> Result<&mut ProcessStates, SimulationError> {
+        let id = self.resolve(reference);
+        self.processes
+            .iter_mut()
+            .find(|p| p.id() == id)
+            .ok_or(SimulationError::ProcessNotFound)
+    }
+
+    fn resource(&self, reference: &str) -> Result<GenericResource, SimulationError> {
+        let id = self.resolve(reference);
+        self.resources
+            .iter()
+            .find(|r| r.id() == id)
+            .cloned()
+            .ok_or(SimulationError::ResourceNotFound)
+    }
+
+    fn apply(&mut self, mutation: Mutation) -> Result<(), SimulationError> {
+        match mutation {
+            Mutation::CreateResource {
+                key,
+                name,
+                total_amount,
+                blocking,
+            } => {
+                validate_name(&name)?;
+                let resource = GenericResource::new(name, total_amount, blocking);
+                self.created.insert(key, resource.id());
+                self.resources.push(resource);
+            }
+            Mutation::CreateProcess {
+                key,
+                name,
+                resource_intensity,
+            } => {
+                validate_name(&name)?;
+                let process = Process::new(name, resource_intensity);
+                self.created.insert(key, process.id());
+                self.processes.push(ProcessStates::Ready(process));
+            }
+            Mutation::AddSlot {
+                process,
+                resource,
+                amount,
+            } => {
+                let resource = self.resource(&resource)?;
+                let process = self.process_mut(&process)?;
+                validate_slot(process.process(), &resource, amount)?;
+                process.process_mut().add_resource(&resource, amount);
+            }
+            Mutation::RemoveSlot { process, resource } => {
+                let resource_id = self.resolve(&resource);
+                self.process_mut(&process)?
+                    .process_mut()
+                    .remove_resource(resource_id)
+                    .ok_or(SimulationError::SlotNotFound)?;
+            }
+            Mutation::RemoveProcess { process } => {
+                let id = self.resolve(&process);
+                let index = self
+                    .processes
+                    .iter()
+                    .position(|p| p.id() == id)
+                    .ok_or(SimulationError::ProcessNotFound)?;
+                self.processes.remove(index);
+            }
+            Mutation::RemoveResource { resource, policy } => {
+                let id = self.resolve(&resource);
+                remove_resource_from(&mut self.resources, &mut self.processes, &id, policy)?;
+            }
+            Mutation::SetResourceTotalAmount {
+                resource,
+                total_amount,
+            } => {
+                let id = self.resolve(&resource);
+                let resource = self
+                    .resources
+                    .iter_mut()
+                    .find(|r| r.id() == id)
+                    .ok_or(SimulationError::ResourceNotFound)?;
+                validate_total_amount(resource, total_amount, &self.processes)?;
+                resource.set_total_amount(total_amount);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies every mutation to a copy of the simulation state and only swaps it in when
+/// all of them succeed. Both locks are held for the whole batch, so the tick thread
+/// never observes a half-built scenario.
+pub fn apply_batch(
+    resources: &Mutex<Vec<GenericResource>>,
+    processes: &Mutex<Vec<ProcessStates>>,
+    mutations: Vec<Mutation>,
+) -> Result<BatchResult, SimulationError> {
+    // Resources before processes, see the lock order on `RunningSimulation`.
+    let mut resources = lock_state(resources)?;
+    let mut processes = lock_state(processes)?;
+
+    let mut batch = Batch {
+        processes: processes.clone(),
+        resources: resources.clone(),
+        created: HashMap::new(),
+    };
+
+    for (index, mutation) in mutations.into_iter().enumerate() {
+        batch
+            .apply(mutation)
+            .map_err(|error| SimulationError::BatchFailed {
+                index,
+                error: Box::new(error),
+            })?;
+    }
+
+    *processes = batch.processes;
+    *resources = batch.resources;
+
+    Ok(BatchResult {
+        processes: processes.clone(),
+        resources: resources.clone(),
+        created: batch.created,
+    })
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_apply_batch(
+    app_handle: tauri::AppHandle,
+    mutations: Vec<Mutation>,
+) -> Result<BatchResult, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+
+    let result = apply_batch(&sim.0.resources(), &sim.0.processes(), mutations)?;
+    sim.0.wake();
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_resource(key: &str, total_amount: u64) -> Mutation {
+        Mutation::CreateResource {
+            key: key.to_string(),
+            name: key.to_string(),
+            total_amount,
+            blocking: false,
+        }
+    }
+
+    fn create_process(key: &str) -> Mutation {
+        Mutation::CreateProcess {
+            key: key.to_string(),
+            name: key.to_string(),
+            resource_intensity: GenericProcessResourceIntensity::Low,
+        }
+    }
+
+    #[test]
+    fn later_mutations_refer_to_earlier_ones_by_key() {
+        let resources = Mutex::new(vec![]);
+        let processes = Mutex::new(vec![]);
+
+        let result = apply_batch(
+            &resources,
+            &processes,
+            vec![
+                create_resource("memory", 100),
+                create_process("worker"),
+                Mutation::AddSlot {
+                    process: "worker".to_string(),
+                    resource: "memory".to_string(),
+                    amount: 10,
+                },
+            ],
+        )
+        .unwrap();
+
+        let resources = resources.lock().unwrap();
+        let processes = processes.lock().unwrap();
+        assert_eq!(result.created("memory"), Some(&resources[0].id()));
+        assert_eq!(result.created("worker"), Some(&processes[0].id()));
+        let slots = processes[0].process().resource_slot();
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].resource_id(), resources[0].id());
+    }
+
+    #[test]
+    fn a_failing_mutation_leaves_the_state_untouched() {
+        let existing = GenericResource::new("CPU".to_string(), 10, false);
+        let resources = Mutex::new(vec![existing.clone()]);
+        let processes = Mutex::new(vec![]);
+
+        let error = apply_batch(
+            &resources,
+            &processes,
+            vec![
+                create_resource("memory", 100),
+                create_process("worker"),
+                Mutation::SetResourceTotalAmount {
+                    resource: existing.id(),
+                    total_amount: 20,
+                },
+                Mutation::AddSlot {
+                    process: "worker".to_string(),
+                    resource: "memory".to_string(),
+                    amount: 1000,
+                },
+            ],
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            SimulationError::BatchFailed {
+                index: 3,
+                error,
+            } if matches!(*error, SimulationError::Invalid(_))
+        ));
+        let resources = resources.lock().unwrap();
+        assert_eq!(resources.len(), 1);
+        assert_eq!(resources[0].total_amount(), 10);
+        assert!(processes.lock().unwrap().is_empty());
+    }
+}
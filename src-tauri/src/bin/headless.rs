@@ -0,0 +1,397 @@
+//! Runs a scenario file without a window and prints what happened.
+//!
+//! headless <scenario.toml|scenario.json> [--ticks N] [--until deadlock|finished] [--seed S] [--json]
+//!
+//! It runs `--ticks` ticks, 1000 by default. With `--until` it stops earlier when the
+//! condition holds.
+//!
+//! Exits with 1 on errors, 2 on bad arguments and 3 when the run hit an unsafe state.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+use app_lib::clock::SimulationTime;
+use app_lib::events::{EventEnvelope, EventSink, ProcessStateKind, SimulationEvent};
+use app_lib::scenario::Scenario;
+use app_lib::simulation::{AllSimulationTrait, RunningSimulation};
+
+const DEFAULT_TICKS: u64 = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Until {
+    Ticks,
+    /// The first time the simulation reaches an unsafe state, or with the discrete
+    /// engine every process is blocked.
+    Deadlock,
+    /// Every process has finished at least one round of work. Processes of the ticked
+    /// engine never work, so only `--ticks` ends those runs.
+    Finished,
+}
+
+struct Options {
+    scenario: PathBuf,
+    ticks: Option<u64>,
+    until: Until,
+    seed: Option<u64>,
+    json: bool,
+}
+
+fn usage() -> String {
+    "Usage: headless <scenario.toml|scenario.json> [--ticks N] [--until deadlock|finished] [--seed S] [--json]"
+        .to_string()
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut scenario = None;
+    let mut options = Options {
+        scenario: PathBuf::new(),
+        ticks: None,
+        until: Until::Ticks,
+        seed: None,
+        json: false,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--ticks" => {
+                options.ticks = Some(
+                    value("--ticks")?
+                        .parse()
+                        .map_err(|_| "--ticks must be a number".to_string())?,
+                )
+            }
+            "--seed" => {
+                options.seed = Some(
+                    value("--seed")?
+                        .parse()
+                        .map_err(|_| "--seed must be a number".to_string())?,
+                )
+            }
+            "--until" => {
+                options.until = match value("--until")?.as_str() {
+                    "deadlock" => Until::Deadlock,
+                    "finished" => Until::Finished,
+                    other => return Err(format!("Unknown condition '{}'", other)),
+                }
+            }
+            "--json" => options.json = true,
+            "--help" | "-h" => return Err(usage()),
+            _ if scenario.is_none() && !arg.starts_with("--") => {
+                scenario = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, usage())),
+        }
+    }
+
+    options.scenario = scenario.ok_or_else(usage)?;
+    Ok(options)
+}
+
+#[derive(Default)]
+struct Tally {
+    transitions: u64,
+    allocations: u64,
+    unsafe_states: Vec<Vec<String>>,
+    /// Processes that went from working back to ready at least once.
+    finished: HashSet<String>,
+}
+
+/// Counts events instead of sending them anywhere.
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Tally>>);
+
+impl EventSink for Collector {
+    fn send(&self, envelope: EventEnvelope) {
+        let Ok(mut tally) = self.0.lock() else {
+            return;
+        };
+
+        match envelope.event() {
+            SimulationEvent::StateTransition {
+                process_id,
+                from: ProcessStateKind::Working,
+                to: ProcessStateKind::Ready,
+            } => {
+                tally.transitions += 1;
+                tally.finished.insert(process_id.clone());
+            }
+            SimulationEvent::StateTransition { .. } => tally.transitions += 1,
+            SimulationEvent::Allocation { .. } => tally.allocations += 1,
+            SimulationEvent::UnsafeState { process_ids } => {
+                tally.unsafe_states.push(process_ids.clone())
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ProcessSummary {
+    id: String,
+    name: String,
+    state: ProcessStateKind,
+}
+
+#[derive(serde::Serialize)]
+struct ResourceSummary {
+    id: String,
+    name: String,
+    free_amount: u64,
+    total_amount: u64,
+}
+
+#[derive(serde::Serialize)]
+struct Report {
+    ticks: u64,
+    time: SimulationTime,
+    stopped_by: &'static str,
+    transitions: u64,
+    allocations: u64,
+    /// Processes marked for deletion every time the simulation became unsafe.
+    unsafe_states: Vec<Vec<String>>,
+    processes: Vec<ProcessSummary>,
+    resources: Vec<ResourceSummary>,
+}
+
+/// Whether every process now in the simulation has finished a round of work.
+fn finished(simulation: &RunningSimulation, sink: &Collector) -> Result<bool, String> {
+    let processes = simulation.processes();
+    let processes = processes.lock().map_err(|e| e.to_string())?;
+    let tally = sink.0.lock().map_err(|e| e.to_string())?;
+    Ok(processes.iter().all(|p| tally.finished.contains(&p.id())))
+}
+
+fn run(options: &Options) -> Result<Report, String> {
+    let scenario = Scenario::read(&options.scenario).map_err(|e| e.to_string())?;
+
+    let sink = Collector::default();
+    let simulation = RunningSimulation::new();
+    simulation
+        .load_scenario(&sink, &scenario)
+        .map_err(|e| e.to_string())?;
+    if let Some(seed) = options.seed {
+        simulation.set_seed(seed).map_err(|e| e.to_string())?;
+    }
+
+    let limit = options.ticks.unwrap_or(DEFAULT_TICKS);
+    let mut ticks = 0;
+    let mut stopped_by = "ticks";
+    while ticks < limit {
+        // Nothing left to run is either an empty simulation or a deadlock the
+        // engine has already reported.
+        let ran = simulation.step(&sink).map_err(|e| e.to_string())?;
+        let deadlocked = sink.0.lock().is_ok_and(|t| !t.unsafe_states.is_empty());
+        if !ran {
+            stopped_by = if deadlocked { "deadlock" } else { "finished" };
+            break;
+        }
+        ticks += 1;
+
+        if options.until == Until::Deadlock && deadlocked {
+            stopped_by = "deadlock";
+            break;
+        }
+        if options.until == Until::Finished && finished(&simulation, &sink)? {
+            stopped_by = "finished";
+            break;
+        }
+    }
+
+    let resources = simulation.resources();
+    let resources = resources.lock().map_err(|e| e.to_string())?;
+    let processes = simulation.processes();
+    let processes = processes.lock().map_err(|e| e.to_string())?;
+    let tally = std::mem::take(&mut *sink.0.lock().map_err(|e| e.to_string())?);
+
+    Ok(Report {
+        ticks,
+        time: simulation.time().map_err(|e| e.to_string())?,
+        stopped_by,
+        transitions: tally.transitions,
+        allocations: tally.allocations,
+        unsafe_states: tally.unsafe_states,
+        processes: processes
+            .iter()
+            .map(|p| ProcessSummary {
+                id: p.id(),
+                name: p.process().name(),
+                state: ProcessStateKind::from(p),
+            })
+            .collect(),
+        resources: resources
+            .iter()
+            .map(|r| ResourceSummary {
+                id: r.id(),
+                name: r.name(),
+                free_amount: r.free_amount(),
+                total_amount: r.total_amount(),
+            })
+            .collect(),
+    })
+}
+
+fn print_summary(report: &Report) {
+    println!(
+        "Ran {} ticks up to time {} (stopped by {})",
+        report.ticks,
+        report.time.time(),
+        report.stopped_by
+    );
+    println!(
+        "{} state transitions, {} allocations, {} unsafe states",
+        report.transitions,
+        report.allocations,
+        report.unsafe_states.len()
+    );
+
+    println!("\nResources:");
+    for resource in &report.resources {
+        println!(
+            "  {:<20} {}/{} free",
+            resource.name, resource.free_amount, resource.total_amount
+        );
+    }
+
+    println!("\nProcesses:");
+    for process in &report.processes {
+        println!("  {:<20} {:?}", process.name, process.state);
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let report = match run(&options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if options.json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        print_summary(&report);
+    }
+
+    // Scripts can tell a deadlocked run apart without parsing the report.
+    if report.unsafe_states.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `scenario` to a file of its own and runs it.
+    fn run_scenario(name: &str, scenario: &str, until: Until, ticks: u64) -> Report {
+        let dir = std::env::temp_dir().join("system-monitor-tests");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("headless-{}-{}.toml", name, std::process::id()));
+        std::fs::write(&path, scenario).unwrap();
+
+        run(&Options {
+            scenario: path,
+            ticks: Some(ticks),
+            until,
+            seed: Some(7),
+            json: false,
+        })
+        .unwrap()
+    }
+
+    fn scenario(engine: &str, total_amount: u64) -> String {
+        format!(
+            r#"
+version = 1
+
+[settings]
+engine = "{}"
+
+[[resources]]
+name = "Memory"
+total_amount = {}
+
+[[processes]]
+name = "A"
+resource_intensity = "High"
+slots = [{{ resource = "Memory", base_amount = 1 }}]
+
+[[processes]]
+name = "B"
+resource_intensity = "High"
+slots = [{{ resource = "Memory", base_amount = 1 }}]
+"#,
+            engine, total_amount
+        )
+    }
+
+    #[test]
+    fn discrete_runs_finish_once_every_process_has_worked() {
+        let report = run_scenario(
+            "discrete-finished",
+            &scenario("DiscreteEvent", 100),
+            Until::Finished,
+            200,
+        );
+        assert_eq!(report.stopped_by, "finished");
+        assert!(report.ticks < 200);
+        assert!(report.unsafe_states.is_empty());
+    }
+
+    #[test]
+    fn ticked_runs_never_finish_on_their_own() {
+        let report = run_scenario(
+            "ticked-finished",
+            &scenario("Ticked", 100),
+            Until::Finished,
+            50,
+        );
+        assert_eq!(report.stopped_by, "ticks");
+        assert_eq!(report.ticks, 50);
+    }
+
+    #[test]
+    fn runs_without_a_deadlock_stop_at_the_tick_limit() {
+        let report = run_scenario(
+            "discrete-deadlock",
+            &scenario("DiscreteEvent", 100),
+            Until::Deadlock,
+            50,
+        );
+        assert_eq!(report.stopped_by, "ticks");
+        assert_eq!(report.ticks, 50);
+    }
+
+    #[test]
+    fn ticked_runs_stop_at_the_first_unsafe_state() {
+        let report = run_scenario(
+            "ticked-deadlock",
+            &scenario("Ticked", 1),
+            Until::Deadlock,
+            200,
+        );
+        assert_eq!(report.stopped_by, "deadlock");
+        assert_eq!(report.unsafe_states.len(), 1);
+    }
+}
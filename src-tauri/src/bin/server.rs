@@ -0,0 +1,107 @@
+//! Serves the simulation over HTTP without a window.
+//!
+//! server [scenario.toml|scenario.json] [--port P] [--token T] [--seed S]
+//!
+//! Binds 127.0.0.1 only. Without `--token` a random one is generated and printed.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+
+use app_lib::api::{ApiServer, Hub};
+use app_lib::scenario::Scenario;
+use app_lib::simulation::RunningSimulation;
+
+const DEFAULT_PORT: u16 = 7878;
+
+struct Options {
+    scenario: Option<PathBuf>,
+    port: u16,
+    token: Option<String>,
+    seed: Option<u64>,
+}
+
+fn usage() -> String {
+    "Usage: server [scenario.toml|scenario.json] [--port P] [--token T] [--seed S]".to_string()
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = std::env::args().skip(1);
+    let mut options = Options {
+        scenario: None,
+        port: DEFAULT_PORT,
+        token: None,
+        seed: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "--port" => {
+                options.port = value("--port")?
+                    .parse()
+                    .map_err(|_| "--port must be a number".to_string())?
+            }
+            "--token" => options.token = Some(value("--token")?),
+            "--seed" => {
+                options.seed = Some(
+                    value("--seed")?
+                        .parse()
+                        .map_err(|_| "--seed must be a number".to_string())?,
+                )
+            }
+            "--help" | "-h" => return Err(usage()),
+            _ if options.scenario.is_none() && !arg.starts_with("--") => {
+                options.scenario = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, usage())),
+        }
+    }
+
+    Ok(options)
+}
+
+fn run(options: Options) -> Result<(), String> {
+    // WebSocket clients get the events straight from the simulation.
+    let hub = Hub::default();
+    let simulation = RunningSimulation::new();
+
+    if let Some(path) = &options.scenario {
+        let scenario = Scenario::read(path).map_err(|e| e.to_string())?;
+        simulation
+            .load_scenario(&hub, &scenario)
+            .map_err(|e| e.to_string())?;
+    }
+    if let Some(seed) = options.seed {
+        simulation.set_seed(seed).map_err(|e| e.to_string())?;
+    }
+    simulation.start(&hub).map_err(|e| e.to_string())?;
+
+    let server = ApiServer::start(simulation, hub.clone(), hub, options.port, options.token)
+        .map_err(|e| e.to_string())?;
+
+    println!("Listening on http://127.0.0.1:{}/api", server.port());
+    println!("Token: {}", server.token());
+
+    loop {
+        thread::park();
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
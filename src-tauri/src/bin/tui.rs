@@ -0,0 +1,734 @@
+//! Terminal front end for the simulator, for when there is no window to open.
+//!
+//! tui [scenario.toml|scenario.json] [--seed S]
+//!
+//! The keys are listed at the bottom of the screen. New processes are typed as
+//! `name intensity [resource=amount ...]` and new resources as `name total [blocking]`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use app_lib::batch::{apply_batch, Mutation};
+use app_lib::discrete::Engine;
+use app_lib::events::{EventEnvelope, EventSink, SimulationEvent};
+use app_lib::generic_process::{GenericProcessResourceIntensity, ProcessStates};
+use app_lib::generic_resource::GenericResource;
+use app_lib::scenario::Scenario;
+use app_lib::simulation::{AllSimulationTrait, RunningSimulation, SimulationError};
+
+/// How long to wait for a key before drawing the next frame.
+const FRAME: Duration = Duration::from_millis(100);
+const MIN_SPEED: f64 = 0.25;
+const MAX_SPEED: f64 = 4096.0;
+
+const HELP: &str = "space pause  s step  +/- speed  e engine  p process  r resource  \
+                    ↑/↓ select  d delete  q quit";
+
+/// Forwards events to the UI thread.
+#[derive(Clone)]
+struct Channel(Sender<EventEnvelope>);
+
+impl EventSink for Channel {
+    fn send(&self, envelope: EventEnvelope) {
+        let _ = self.0.send(envelope);
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Prompt {
+    Process,
+    Resource,
+}
+
+struct Input {
+    prompt: Prompt,
+    text: String,
+}
+
+struct App {
+    simulation: RunningSimulation,
+    sink: Channel,
+    events: Receiver<EventEnvelope>,
+    /// Speed to go back to when resuming.
+    speed: f64,
+    paused: bool,
+    tick_rate: f64,
+    /// Last unsafe state or worker failure, cleared when resuming.
+    alert: Option<String>,
+    /// Result of the last command.
+    status: String,
+    input: Option<Input>,
+    /// Index into the process list.
+    selected: usize,
+    processes: Vec<ProcessStates>,
+    resources: Vec<GenericResource>,
+}
+
+impl App {
+    fn new(simulation: RunningSimulation) -> Result<Self, SimulationError> {
+        let (tx, events) = channel();
+        let speed = *simulation.simulation_speed().lock()?;
+
+        Ok(App {
+            simulation,
+            sink: Channel(tx),
+            events,
+            speed: if speed > 0.0 { speed } else { 1.0 },
+            paused: speed == 0.0,
+            tick_rate: 0.0,
+            alert: None,
+            status: String::new(),
+            input: None,
+            selected: 0,
+            processes: vec![],
+            resources: vec![],
+        })
+    }
+
+    /// Copies the current state so drawing never holds the simulation locks.
+    fn refresh(&mut self) -> Result<(), SimulationError> {
+        // Resources before processes, see the lock order on `RunningSimulation`.
+        let resources = self.simulation.resources();
+        let resources = resources.lock()?;
+        let processes = self.simulation.processes();
+        let processes = processes.lock()?;
+
+        self.resources = resources.clone();
+        self.processes = processes.clone();
+        self.selected = self.selected.min(self.processes.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn drain_events(&mut self) {
+        while let Ok(envelope) = self.events.try_recv() {
+            match envelope.event() {
+                SimulationEvent::UnsafeState { process_ids } => {
+                    // The simulation stops itself when it becomes unsafe.
+                    self.paused = true;
+                    let names: Vec<String> = process_ids
+                        .iter()
+                        .map(|id| {
+                            self.processes
+                                .iter()
+                                .find(|p| &p.id() == id)
+                                .map_or(id.clone(), |p| p.process().name())
+                        })
+                        .collect();
+                    self.alert = Some(if names.is_empty() {
+                        "Unsafe state, paused".to_string()
+                    } else {
+                        format!("Unsafe state, paused. Remove: {}", names.join(", "))
+                    });
+                }
+                SimulationEvent::WorkerDied { reason } => {
+                    self.paused = true;
+                    self.alert = Some(format!("Simulation stopped: {}", reason));
+                }
+                SimulationEvent::TickRate { ticks_per_second } => {
+                    self.tick_rate = *ticks_per_second
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn apply_speed(&mut self) -> Result<(), SimulationError> {
+        let speed = if self.paused { 0.0 } else { self.speed };
+        self.simulation.clone().set_simulation_speed(speed)?;
+        self.simulation.wake();
+        Ok(())
+    }
+
+    fn toggle_pause(&mut self) -> Result<(), SimulationError> {
+        if self.paused {
+            self.paused = false;
+            self.alert = None;
+            self.apply_speed()
+        } else {
+            self.paused = true;
+            self.simulation.stop(&self.sink)
+        }
+    }
+
+    fn step(&mut self) -> Result<(), SimulationError> {
+        if !self.paused {
+            self.status = "Pause before stepping".to_string();
+            return Ok(());
+        }
+
+        if !self.simulation.step(&self.sink)? {
+            self.status = "Nothing left to run".to_string();
+        }
+        Ok(())
+    }
+
+    fn change_speed(&mut self, factor: f64) -> Result<(), SimulationError> {
+        self.speed = (self.speed * factor).clamp(MIN_SPEED, MAX_SPEED);
+        if !self.paused {
+            self.apply_speed()?;
+        }
+        Ok(())
+    }
+
+    fn toggle_engine(&mut self) -> Result<(), SimulationError> {
+        let engine = match self.simulation.engine()? {
+            Engine::Ticked => Engine::DiscreteEvent,
+            Engine::DiscreteEvent => Engine::Ticked,
+        };
+        self.simulation.set_engine(engine)?;
+        self.simulation.wake();
+        Ok(())
+    }
+
+    fn delete_selected(&mut self) -> Result<(), SimulationError> {
+        let Some(process) = self.processes.get(self.selected) else {
+            return Ok(());
+        };
+        let name = process.process().name();
+
+        self.apply(vec![Mutation::RemoveProcess {
+            process: process.id(),
+        }])?;
+        self.status = format!("Removed {}", name);
+        Ok(())
+    }
+
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<(), SimulationError> {
+        apply_batch(
+            &self.simulation.resources(),
+            &self.simulation.processes(),
+            mutations,
+        )?;
+        self.simulation.wake();
+        Ok(())
+    }
+
+    fn submit(&mut self, input: Input) -> Result<(), String> {
+        let mutations = match input.prompt {
+            Prompt::Process => self.parse_process(&input.text)?,
+            Prompt::Resource => parse_resource(&input.text)?,
+        };
+        self.apply(mutations).map_err(|e| e.to_string())
+    }
+
+    /// `name intensity [resource=amount ...]`, resources are looked up by name.
+    fn parse_process(&self, text: &str) -> Result<Vec<Mutation>, String> {
+        let mut words = text.split_whitespace();
+        let name = words.next().ok_or("Missing process name")?;
+        let resource_intensity = match words.next().map(str::to_lowercase).as_deref() {
+            Some("none") => GenericProcessResourceIntensity::None,
+            Some("low") => GenericProcessResourceIntensity::Low,
+            Some("medium") => GenericProcessResourceIntensity::Medium,
+            Some("high") => GenericProcessResourceIntensity::High,
+            Some("extreme") => GenericProcessResourceIntensity::Extreme,
+            _ => return Err("Intensity must be none, low, medium, high or extreme".to_string()),
+        };
+
+        let mut mutations = vec![Mutation::CreateProcess {
+            key: "process".to_string(),
+            name: name.to_string(),
+            resource_intensity,
+        }];
+        for slot in words {
+            let (resource, amount) = slot
+                .split_once('=')
+                .ok_or(format!("Expected resource=amount, got '{}'", slot))?;
+            let resource = self
+                .resources
+                .iter()
+                .find(|r| r.name() == resource)
+                .ok_or(format!("No resource called '{}'", resource))?;
+            mutations.push(Mutation::AddSlot {
+                process: "process".to_string(),
+                resource: resource.id(),
+                amount: amount
+                    .parse()
+                    .map_err(|_| format!("'{}' is not an amount", amount))?,
+            });
+        }
+
+        Ok(mutations)
+    }
+
+    /// Returns false when the user asked to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(mut input) = self.input.take() {
+            match key.code {
+                KeyCode::Enter => {
+                    self.status = match self.submit(input) {
+                        Ok(()) => "Added".to_string(),
+                        Err(e) => e,
+                    };
+                }
+                KeyCode::Esc => {}
+                KeyCode::Backspace => {
+                    input.text.pop();
+                    self.input = Some(input);
+                }
+                KeyCode::Char(c) => {
+                    input.text.push(c);
+                    self.input = Some(input);
+                }
+                _ => self.input = Some(input),
+            }
+            return true;
+        }
+
+        let result = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char(' ') => self.toggle_pause(),
+            KeyCode::Char('s') => self.step(),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.change_speed(2.0),
+            KeyCode::Char('-') => self.change_speed(0.5),
+            KeyCode::Char('e') => self.toggle_engine(),
+            KeyCode::Char('d') | KeyCode::Delete => self.delete_selected(),
+            KeyCode::Char('p') => {
+                self.input = Some(Input {
+                    prompt: Prompt::Process,
+                    text: String::new(),
+                });
+                Ok(())
+            }
+            KeyCode::Char('r') => {
+                self.input = Some(Input {
+                    prompt: Prompt::Resource,
+                    text: String::new(),
+                });
+                Ok(())
+            }
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                Ok(())
+            }
+            KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.processes.len().saturating_sub(1));
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.status = e.to_string();
+        }
+        true
+    }
+}
+
+/// `name total [blocking]`
+fn parse_resource(text: &str) -> Result<Vec<Mutation>, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let (name, total, blocking) = match words.as_slice() {
+        [name, total] => (name, total, false),
+        [name, total, "blocking"] => (name, total, true),
+        _ => return Err("Expected: name total [blocking]".to_string()),
+    };
+
+    Ok(vec![Mutation::CreateResource {
+        key: "resource".to_string(),
+        name: name.to_string(),
+        total_amount: total
+            .parse()
+            .map_err(|_| format!("'{}' is not an amount", total))?,
+        blocking,
+    }])
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let alert_height = if app.alert.is_some() { 3 } else { 0 };
+    let [header, body, alert, footer] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(alert_height),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [processes, resources] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(body);
+
+    draw_header(frame, app, header);
+    draw_processes(frame, app, processes);
+    draw_resources(frame, app, resources);
+
+    if let Some(message) = &app.alert {
+        let style = Style::default().fg(Color::White).bg(Color::Red);
+        frame.render_widget(
+            Paragraph::new(message.as_str())
+                .style(style)
+                .block(Block::bordered().title("Alert")),
+            alert,
+        );
+    }
+
+    let footer_text = match &app.input {
+        Some(input) => {
+            let prompt = match input.prompt {
+                Prompt::Process => "New process (name intensity [resource=amount ...])",
+                Prompt::Resource => "New resource (name total [blocking])",
+            };
+            format!("{}: {}_", prompt, input.text)
+        }
+        None if !app.status.is_empty() => format!("{}  |  {}", app.status, HELP),
+        None => HELP.to_string(),
+    };
+    frame.render_widget(
+        Paragraph::new(footer_text).style(Style::default().add_modifier(Modifier::DIM)),
+        footer,
+    );
+}
+
+fn draw_header(frame: &mut Frame, app: &App, area: Rect) {
+    let time = app.simulation.time().unwrap_or_default();
+    let engine = app.simulation.engine().unwrap_or_default();
+    let state = if app.paused { "paused" } else { "running" };
+
+    let header = format!(
+        " {}  |  tick {}  time {:.2}  |  speed {} ({:.1} ticks/s)  |  {:?}",
+        state,
+        time.tick(),
+        time.time(),
+        app.speed,
+        app.tick_rate,
+        engine
+    );
+    frame.render_widget(
+        Paragraph::new(header).style(Style::default().add_modifier(Modifier::BOLD)),
+        area,
+    );
+}
+
+fn draw_processes(frame: &mut Frame, app: &App, area: Rect) {
+    let names: HashMap<String, String> = app.resources.iter().map(|r| (r.id(), r.name())).collect();
+
+    let areas = Layout::vertical([Constraint::Ratio(1, 3); 3]).split(area);
+    let tables = [
+        ("Ready", Color::Green),
+        ("Blocked", Color::Yellow),
+        ("Working", Color::Cyan),
+    ];
+
+    for (index, (title, color)) in tables.into_iter().enumerate() {
+        let rows = app
+            .processes
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                matches!(
+                    (index, p),
+                    (0, ProcessStates::Ready(_))
+                        | (1, ProcessStates::Blocked(_))
+                        | (2, ProcessStates::Working(_))
+                )
+            })
+            .map(|(position, p)| {
+                let process = p.process();
+                let slots: Vec<String> = process
+                    .resource_slot()
+                    .iter()
+                    .map(|slot| {
+                        let name = names.get(&slot.resource_id()).map_or("?", |n| n.as_str());
+                        format!("{} {}/{}", name, slot.current_amount(), slot.base_amount())
+                    })
+                    .collect();
+
+                let row = Row::new(vec![
+                    process.name(),
+                    format!("{:?}", process.resource_intensity()),
+                    slots.join(", "),
+                ]);
+                if position == app.selected {
+                    row.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    row
+                }
+            });
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(16),
+                Constraint::Length(8),
+                Constraint::Min(0),
+            ],
+        )
+        .header(Row::new(["Name", "Load", "Slots (current/base)"]))
+        .block(
+            Block::bordered()
+                .title(title)
+                .border_style(Style::default().fg(color)),
+        );
+        frame.render_widget(table, areas[index]);
+    }
+}
+
+fn draw_resources(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::bordered().title("Resources");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    if app.resources.is_empty() {
+        frame.render_widget(Line::from("No resources, press r to add one"), inner);
+        return;
+    }
+
+    let rows = Layout::vertical(vec![Constraint::Length(2); app.resources.len()]).split(inner);
+    for (resource, row) in app.resources.iter().zip(rows.iter()) {
+        let total = resource.total_amount();
+        let used = total.saturating_sub(resource.free_amount());
+        let requested: u64 = app
+            .processes
+            .iter()
+            .flat_map(|p| p.process().resource_slot())
+            .filter(|slot| slot.resource_id() == resource.id())
+            .map(|slot| slot.current_amount())
+            .sum();
+
+        // Red once the requests can no longer be covered by what is left.
+        let color = if requested > resource.free_amount() {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        let ratio = if total == 0 {
+            0.0
+        } else {
+            used as f64 / total as f64
+        };
+
+        let [title, bar] =
+            Layout::vertical([Constraint::Length(1), Constraint::Length(1)]).areas(*row);
+        frame.render_widget(
+            Line::from(format!(
+                "{}{}",
+                resource.name(),
+                if resource.blocking() {
+                    " (blocking)"
+                } else {
+                    ""
+                }
+            )),
+            title,
+        );
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(color))
+                .ratio(ratio)
+                .label(format!(
+                    "{}/{} in use, {} requested",
+                    used, total, requested
+                )),
+            bar,
+        );
+    }
+}
+
+fn run(terminal: &mut DefaultTerminal, mut app: App) -> Result<(), Box<dyn Error>> {
+    app.simulation.start(&app.sink)?;
+
+    loop {
+        app.refresh()?;
+        app.drain_events();
+        terminal.draw(|frame| draw(frame, &app))?;
+
+        if event::poll(FRAME)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+fn setup() -> Result<RunningSimulation, String> {
+    let usage = "Usage: tui [scenario.toml|scenario.json] [--seed S]";
+    let mut args = std::env::args().skip(1);
+    let mut scenario = None;
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                seed = Some(
+                    args.next()
+                        .and_then(|s| s.parse::<u64>().ok())
+                        .ok_or("--seed needs a number")?,
+                )
+            }
+            "--help" | "-h" => return Err(usage.to_string()),
+            _ if scenario.is_none() && !arg.starts_with("--") => {
+                scenario = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument '{}'\n{}", arg, usage)),
+        }
+    }
+
+    let simulation = RunningSimulation::new();
+    if let Some(path) = scenario {
+        let scenario = Scenario::read(&path).map_err(|e| e.to_string())?;
+        // Nothing listens yet, the first frame reads the state directly.
+        let (tx, _) = channel();
+        simulation
+            .load_scenario(&Channel(tx), &scenario)
+            .map_err(|e| e.to_string())?;
+    }
+    if let Some(seed) = seed {
+        simulation.set_seed(seed).map_err(|e| e.to_string())?;
+    }
+
+    Ok(simulation)
+}
+
+fn main() -> ExitCode {
+    let simulation = match setup() {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(2);
+        }
+    };
+    let app = match App::new(simulation) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, app);
+    ratatui::restore();
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An app whose simulation has a single resource called Memory.
+    fn app() -> (App, String) {
+        let mut simulation = RunningSimulation::new();
+        let memory = GenericResource::new("Memory".to_string(), 100, false);
+        let memory_id = memory.id();
+        simulation.add_resource(memory).unwrap();
+
+        let mut app = App::new(simulation).unwrap();
+        app.refresh().unwrap();
+        (app, memory_id)
+    }
+
+    #[test]
+    fn process_with_slots() {
+        let (app, memory_id) = app();
+
+        let mutations = app.parse_process("editor HIGH Memory=20").unwrap();
+        assert_eq!(mutations.len(), 2);
+        assert!(matches!(
+            &mutations[0],
+            Mutation::CreateProcess {
+                name,
+                resource_intensity: GenericProcessResourceIntensity::High,
+                ..
+            } if name == "editor"
+        ));
+        assert!(matches!(
+            &mutations[1],
+            Mutation::AddSlot { process, resource, amount: 20 }
+                if process == "process" && *resource == memory_id
+        ));
+
+        assert_eq!(app.parse_process("editor none").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn process_with_bad_fields() {
+        let (app, _) = app();
+
+        for text in ["", "   ", "editor", "editor fast"] {
+            assert!(app.parse_process(text).is_err(), "{:?}", text);
+        }
+        assert_eq!(
+            app.parse_process("editor low Memory=lots").unwrap_err(),
+            "'lots' is not an amount"
+        );
+        assert_eq!(
+            app.parse_process("editor low Memory=-5").unwrap_err(),
+            "'-5' is not an amount"
+        );
+        assert_eq!(
+            app.parse_process("editor low Memory=").unwrap_err(),
+            "'' is not an amount"
+        );
+        assert_eq!(
+            app.parse_process("editor low =5").unwrap_err(),
+            "No resource called ''"
+        );
+        assert_eq!(
+            app.parse_process("editor low Memory").unwrap_err(),
+            "Expected resource=amount, got 'Memory'"
+        );
+        assert_eq!(
+            app.parse_process("editor low Disk=5").unwrap_err(),
+            "No resource called 'Disk'"
+        );
+    }
+
+    #[test]
+    fn resource() {
+        assert!(matches!(
+            parse_resource("Memory 512").unwrap().as_slice(),
+            [Mutation::CreateResource { name, total_amount: 512, blocking: false, .. }]
+                if name == "Memory"
+        ));
+        assert!(matches!(
+            parse_resource("  Disk   64 blocking ").unwrap().as_slice(),
+            [Mutation::CreateResource {
+                total_amount: 64,
+                blocking: true,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn resource_with_bad_fields() {
+        for text in [
+            "",
+            "Memory",
+            "Memory 512 shared",
+            "Memory 512 blocking extra",
+        ] {
+            assert_eq!(
+                parse_resource(text).unwrap_err(),
+                "Expected: name total [blocking]",
+                "{:?}",
+                text
+            );
+        }
+        assert_eq!(
+            parse_resource("Memory 1.5").unwrap_err(),
+            "'1.5' is not an amount"
+        );
+        assert_eq!(
+            parse_resource("Memory -1").unwrap_err(),
+            "'-1' is not an amount"
+        );
+    }
+}
@@ -0,0 +1,409 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+use crate::generic_process::{
+    AllProcessTraits, GenericProcessResourceIntensity, Process, ProcessStates,
+};
+use crate::generic_resource::GenericResource;
+use crate::procfs::{self, SystemError, SystemInfo, PROC_ROOT};
+use crate::simulation::{safe_to_continue, SimulationError};
+#[cfg(feature = "desktop")]
+use crate::{simulation::lock_state, snapshot::Snapshot, TauriSim};
+
+/// Limits and usage of one cgroup v2 directory. A limit of `None` is `max`, or a
+/// controller that is not enabled there.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct CgroupInfo {
+    pub name: String,
+    pub memory_max_kib: Option<u64>,
+    pub memory_current_kib: u64,
+    /// Thousandths of a core, from the quota and period in `cpu.max`.
+    pub cpu_max_millicores: Option<u64>,
+    pub pids_max: Option<u64>,
+    pub pids_current: u64,
+}
+
+/// Contents of a file, or `None` when the controller does not provide it here.
+fn optional(path: &Path) -> Result<Option<String>, SystemError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(SystemError::Io(format!("{}: {}", path.display(), e))),
+    }
+}
+
+fn parse(path: &Path, value: &str) -> Result<u64, SystemError> {
+    value
+        .parse()
+        .map_err(|_| SystemError::Parse(format!("invalid '{}' in {}", value, path.display())))
+}
+
+/// `max` or a number, as in `memory.max` and `pids.max`.
+fn limit(path: &Path) -> Result<Option<u64>, SystemError> {
+    match optional(path)?.as_deref().map(str::trim) {
+        None | Some("max") => Ok(None),
+        Some(value) => parse(path, value).map(Some),
+    }
+}
+
+/// A number that is 0 when the controller does not provide it here.
+fn current(path: &Path) -> Result<u64, SystemError> {
+    match optional(path)? {
+        Some(value) => parse(path, value.trim()),
+        None => Ok(0),
+    }
+}
+
+impl CgroupInfo {
+    pub fn read(dir: &Path) -> Result<Self, SystemError> {
+        if !dir.join("cgroup.controllers").exists() {
+            return Err(SystemError::NotCgroup(dir.display().to_string()));
+        }
+
+        let cpu_max = dir.join("cpu.max");
+        let cpu_max_millicores = match optional(&cpu_max)? {
+            Some(contents) => match contents.split_whitespace().collect::<Vec<_>>()[..] {
+                ["max", ..] => None,
+                [quota, period] => {
+                    let period = parse(&cpu_max, period)?.max(1);
+                    Some(parse(&cpu_max, quota)? * 1000 / period)
+                }
+                _ => return Err(SystemError::Parse(format!("invalid {}", cpu_max.display()))),
+            },
+            None => None,
+        };
+
+        Ok(CgroupInfo {
+            name: dir.file_name().map_or(dir.display().to_string(), |name| {
+                name.to_string_lossy().to_string()
+            }),
+            memory_max_kib: limit(&dir.join("memory.max"))?.map(|bytes| bytes / 1024),
+            memory_current_kib: current(&dir.join("memory.current"))? / 1024,
+            cpu_max_millicores,
+            pids_max: limit(&dir.join("pids.max"))?,
+            pids_current: current(&dir.join("pids.current"))?,
+        })
+    }
+
+    /// The cgroups directly below this one, by name.
+    pub fn read_children(dir: &Path) -> Result<Vec<Self>, SystemError> {
+        let entries =
+            fs::read_dir(dir).map_err(|e| SystemError::Io(format!("{}: {}", dir.display(), e)))?;
+
+        let mut children = vec![];
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                children.push(Self::read(&entry.path())?);
+            }
+        }
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(children)
+    }
+}
+
+/// What the machine has, for the limits a cgroup leaves at `max`.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct MachineLimits {
+    pub mem_total_kib: u64,
+    pub cores: u64,
+    pub pid_max: u64,
+}
+
+impl MachineLimits {
+    pub fn read(root: &Path) -> Result<Self, SystemError> {
+        let system = SystemInfo::read(root)?;
+        let pid_max = procfs::read(&root.join("sys/kernel/pid_max"))?;
+
+        Ok(MachineLimits {
+            mem_total_kib: system.mem_total_kib,
+            cores: system.cores,
+            pid_max: parse(&root.join("sys/kernel/pid_max"), pid_max.trim())?,
+        })
+    }
+}
+
+/// Resources for memory (MiB), CPU (millicores) and pids, sized by the limits of
+/// `parent`.
+fn resources(machine: &MachineLimits, parent: &CgroupInfo) -> [GenericResource; 3] {
+    [
+        GenericResource::new(
+            "Memory (MiB)".to_string(),
+            parent.memory_max_kib.unwrap_or(machine.mem_total_kib) / 1024,
+            false,
+        ),
+        GenericResource::new(
+            "CPU (millicores)".to_string(),
+            parent.cpu_max_millicores.unwrap_or(machine.cores * 1000),
+            false,
+        ),
+        GenericResource::new(
+            "Pids".to_string(),
+            parent.pids_max.unwrap_or(machine.pid_max),
+            false,
+        ),
+    ]
+}
+
+/// What a child may grow to of each resource, all of it when it has no limit.
+fn claims(child: &CgroupInfo, resources: &[GenericResource; 3]) -> [u64; 3] {
+    let limits = [
+        child.memory_max_kib.map(|kib| kib / 1024),
+        child.cpu_max_millicores,
+        child.pids_max,
+    ];
+    let mut claims = [0; 3];
+    for (claim, (limit, resource)) in claims.iter_mut().zip(limits.iter().zip(resources)) {
+        *claim = limit.unwrap_or(u64::MAX).min(resource.total_amount());
+    }
+    claims
+}
+
+/// What a child holds now. CPU time is not held, so there is nothing to count for it.
+fn usage(child: &CgroupInfo) -> [u64; 3] {
+    [child.memory_current_kib / 1024, 0, child.pids_current]
+}
+
+fn process(name: String, resources: &[GenericResource], amounts: [u64; 3]) -> ProcessStates {
+    let mut process = Process::new(name, GenericProcessResourceIntensity::Low);
+    for (resource, amount) in resources.iter().zip(amounts) {
+        process.add_resource(resource, amount);
+    }
+    ProcessStates::Ready(process)
+}
+
+/// Builds the resources of `parent` and one process per child claiming its own limits.
+pub fn build(
+    machine: &MachineLimits,
+    parent: &CgroupInfo,
+    children: &[CgroupInfo],
+) -> (Vec<GenericResource>, Vec<ProcessStates>) {
+    let resources = resources(machine, parent);
+    let processes = children
+        .iter()
+        .map(|child| process(child.name.clone(), &resources, claims(child, &resources)))
+        .collect();
+
+    (resources.to_vec(), processes)
+}
+
+/// Reads a cgroup and its children, with the machine under `root` for what they leave
+/// at `max`.
+pub fn import(
+    dir: &Path,
+    root: &Path,
+) -> Result<(Vec<GenericResource>, Vec<ProcessStates>), SimulationError> {
+    if !cfg!(target_os = "linux") {
+        return Err(SystemError::Unsupported.into());
+    }
+
+    let machine = MachineLimits::read(root)?;
+    Ok(build(
+        &machine,
+        &CgroupInfo::read(dir)?,
+        &CgroupInfo::read_children(dir)?,
+    ))
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct CgroupReport {
+    pub cgroup: CgroupInfo,
+    pub children: Vec<CgroupInfo>,
+    /// Whether every child can still grow to its limits, one after another, with what
+    /// the parent has left after what they all use now. Like the simulation this never
+    /// counts what a child gives back, so it only errs on the side of caution.
+    pub safe: bool,
+}
+
+impl CgroupReport {
+    pub fn read(dir: &Path, root: &Path) -> Result<Self, SimulationError> {
+        if !cfg!(target_os = "linux") {
+            return Err(SystemError::Unsupported.into());
+        }
+
+        let machine = MachineLimits::read(root)?;
+        let cgroup = CgroupInfo::read(dir)?;
+        let children = CgroupInfo::read_children(dir)?;
+        let resources = resources(&machine, &cgroup);
+
+        // Same check as `Environment::baseline_action`: what is free stands in for the
+        // total and what is left to claim for the request.
+        let free: Vec<GenericResource> = resources
+            .iter()
+            .enumerate()
+            .map(|(j, resource)| {
+                let used = children.iter().map(|c| usage(c)[j]).sum::<u64>();
+                GenericResource::new(
+                    resource.name(),
+                    resource.total_amount().saturating_sub(used),
+                    resource.blocking(),
+                )
+            })
+            .collect();
+
+        let remaining = children
+            .iter()
+            .map(|child| {
+                let mut left = claims(child, &resources);
+                for (left, used) in left.iter_mut().zip(usage(child)) {
+                    *left = left.saturating_sub(used);
+                }
+                process(child.name.clone(), &free, left)
+            })
+            .collect();
+
+        Ok(CgroupReport {
+            cgroup,
+            children,
+            safe: safe_to_continue(remaining, free),
+        })
+    }
+}
+
+/// Limits and usage of a cgroup v2 directory and its children, and whether the
+/// children can all reach their limits.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn system_cgroup_report(path: String) -> Result<CgroupReport, SimulationError> {
+    CgroupReport::read(Path::new(&path), Path::new(PROC_ROOT))
+}
+
+/// Replaces the simulation with the children of a cgroup v2 directory.
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_import_cgroup(
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<Snapshot, SimulationError> {
+    let (resources, processes) = import(Path::new(&path), Path::new(PROC_ROOT))?;
+
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.replace(&app_handle, resources, processes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::tests::temp_dir;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn write(dir: &Path, files: &[(&str, String)]) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("cgroup.controllers"), "cpu memory pids\n").unwrap();
+        for (name, contents) in files {
+            fs::write(dir.join(name), format!("{}\n", contents)).unwrap();
+        }
+    }
+
+    /// A machine with 8 GiB and 2 cores, and a cgroup of 1 GiB with two children.
+    fn machine(name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+        let root = temp_dir(name);
+        fs::create_dir_all(root.join("sys/fs")).unwrap();
+        fs::create_dir_all(root.join("sys/kernel")).unwrap();
+        fs::write(root.join("meminfo"), "MemTotal:       8388608 kB\n").unwrap();
+        fs::write(root.join("cpuinfo"), "processor\t: 0\n\nprocessor\t: 1\n").unwrap();
+        fs::write(root.join("sys/fs/file-max"), "1000\n").unwrap();
+        fs::write(root.join("sys/kernel/pid_max"), "4096\n").unwrap();
+
+        let parent = root.join("cgroup");
+        write(
+            &parent,
+            &[
+                ("memory.max", (1024 * MIB).to_string()),
+                ("cpu.max", "max 100000".to_string()),
+                ("pids.max", "max".to_string()),
+            ],
+        );
+        write(
+            &parent.join("a"),
+            &[
+                ("memory.max", (512 * MIB).to_string()),
+                ("memory.current", (100 * MIB).to_string()),
+                ("cpu.max", "50000 100000".to_string()),
+                ("pids.max", "100".to_string()),
+                ("pids.current", "10".to_string()),
+            ],
+        );
+        write(
+            &parent.join("b"),
+            &[
+                ("memory.max", (500 * MIB).to_string()),
+                ("cpu.max", "100000 100000".to_string()),
+                ("pids.max", "50".to_string()),
+            ],
+        );
+        (root, parent)
+    }
+
+    #[test]
+    fn limits_and_usage_are_read_from_the_controller_files() {
+        let (_, parent) = machine("cgroup-read");
+        let children = CgroupInfo::read_children(&parent).unwrap();
+
+        assert_eq!(
+            children[0],
+            CgroupInfo {
+                name: "a".to_string(),
+                memory_max_kib: Some(512 * 1024),
+                memory_current_kib: 100 * 1024,
+                cpu_max_millicores: Some(500),
+                pids_max: Some(100),
+                pids_current: 10,
+            }
+        );
+        assert_eq!(children[1].name, "b");
+        assert_eq!(children[1].memory_current_kib, 0);
+
+        let parent = CgroupInfo::read(&parent).unwrap();
+        assert_eq!(parent.cpu_max_millicores, None);
+        assert_eq!(parent.pids_max, None);
+    }
+
+    #[test]
+    fn directories_without_controllers_are_not_cgroups() {
+        let dir = temp_dir("cgroup-plain");
+        assert_eq!(
+            CgroupInfo::read(&dir).unwrap_err(),
+            SystemError::NotCgroup(dir.display().to_string())
+        );
+    }
+
+    #[test]
+    fn import_sizes_resources_by_the_parent_and_claims_by_the_children() {
+        let (root, parent) = machine("cgroup-import");
+        let (resources, processes) = import(&parent, &root).unwrap();
+
+        let totals: Vec<u64> = resources.iter().map(|r| r.total_amount()).collect();
+        assert_eq!(totals, vec![1024, 2000, 4096]);
+        let claims: Vec<Vec<u64>> = processes
+            .iter()
+            .map(|p| {
+                p.process()
+                    .resource_slot()
+                    .iter()
+                    .map(|slot| slot.base_amount())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(claims, vec![vec![512, 500, 100], vec![500, 1000, 50]]);
+    }
+
+    #[test]
+    fn children_are_safe_while_their_limits_fit_in_what_is_left() {
+        let (root, parent) = machine("cgroup-report");
+        let report = CgroupReport::read(&parent, &root).unwrap();
+        assert_eq!(report.children.len(), 2);
+        assert!(report.safe);
+
+        // 412 MiB left to claim for a and 1024 for b, with 924 free.
+        fs::write(parent.join("b/memory.max"), "max\n").unwrap();
+        assert!(!CgroupReport::read(&parent, &root).unwrap().safe);
+    }
+}
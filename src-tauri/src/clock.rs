@@ -0,0 +1,115 @@
+/// Point in simulated time, attached to every snapshot and event.
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Debug, Default)]
+pub struct SimulationTime {
+    /// Ticks run since the simulation was created.
+    tick: u64,
+    /// Simulated time units elapsed, the sum of the time step of every tick.
+    time: f64,
+}
+
+impl SimulationTime {
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+}
+
+/// Counts ticks and simulated time independently of wall-clock time.
+///
+/// The speed multiplier is in ticks per real second, so `0.5` runs one tick every
+/// two seconds.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct VirtualClock {
+    now: SimulationTime,
+    /// Simulated time units that pass in one tick.
+    time_step: f64,
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self {
+            now: SimulationTime::default(),
+            time_step: 1.0,
+        }
+    }
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn now(&self) -> SimulationTime {
+        self.now
+    }
+
+    pub fn time_step(&self) -> f64 {
+        self.time_step
+    }
+
+    pub fn set_time_step(&mut self, time_step: f64) {
+        self.time_step = time_step;
+    }
+
+    /// Moves the clock forward by one tick and returns the new time.
+    pub fn advance(&mut self) -> SimulationTime {
+        self.now.tick += 1;
+        self.now.time += self.time_step;
+        self.now
+    }
+
+    /// Moves the clock forward by one tick straight to `time`, for engines that skip
+    /// the time in between.
+    pub fn advance_to(&mut self, time: f64) -> SimulationTime {
+        self.now.tick += 1;
+        self.now.time = self.now.time.max(time);
+        self.now
+    }
+
+    /// Puts the clock back at `time`, e.g. when restoring a checkpoint.
+    pub fn reset_to(&mut self, time: SimulationTime) {
+        self.now = time;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_tick_adds_the_time_step() {
+        let mut clock = VirtualClock::new();
+        clock.advance();
+        clock.set_time_step(0.25);
+        clock.advance();
+        let now = clock.advance();
+
+        assert_eq!(now.tick(), 3);
+        assert_eq!(now.time(), 1.5);
+        assert_eq!(clock.now(), now);
+    }
+
+    #[test]
+    fn jumps_never_go_back_in_time() {
+        let mut clock = VirtualClock::new();
+        assert_eq!(clock.advance_to(10.0).time(), 10.0);
+
+        let now = clock.advance_to(4.0);
+        assert_eq!(now.tick(), 2);
+        assert_eq!(now.time(), 10.0);
+    }
+
+    #[test]
+    fn reset_puts_back_an_earlier_time() {
+        let mut clock = VirtualClock::new();
+        let earlier = clock.advance();
+        clock.advance();
+        clock.reset_to(earlier);
+
+        assert_eq!(clock.now(), earlier);
+        assert_eq!(clock.advance().tick(), 2);
+    }
+}
@@ -0,0 +1,406 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread;
+
+use serde_json::{json, Value};
+use tauri::{AppHandle, Listener, Manager};
+
+use crate::autosave::{self, AutosaveSettings};
+use crate::batch::{self, Mutation};
+use crate::cgroup;
+use crate::discrete::Engine;
+use crate::events::EVENT_CHANNEL;
+use crate::generic_process::{self, GenericProcessResourceIntensity};
+use crate::generic_resource::{self, GenericResource};
+use crate::locks;
+use crate::monitor;
+use crate::procfs;
+use crate::save_state;
+use crate::scenario;
+use crate::simulation::{self, ResourceRemovalPolicy, SimulationError, SimulationHealth};
+
+const SOCKET_FILE: &str = "control.sock";
+/// Messages waiting to be written to one client.
+const QUEUE_SIZE: usize = 1024;
+
+// Codes from the JSON-RPC 2.0 spec.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Any error returned by the command itself.
+const COMMAND_FAILED: i64 = -32000;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// What a command returns, turned into the `result` of a response.
+trait Reply {
+    fn reply(self) -> Result<Value, RpcError>;
+}
+
+impl<T: serde::Serialize, E: ToString> Reply for Result<T, E> {
+    fn reply(self) -> Result<Value, RpcError> {
+        let value = self.map_err(|e| RpcError::new(COMMAND_FAILED, e))?;
+        serde_json::to_value(value).map_err(|e| RpcError::new(COMMAND_FAILED, e))
+    }
+}
+
+/// Commands that cannot fail.
+macro_rules! infallible_reply {
+    ($($t:ty),+) => {
+        $(impl Reply for $t {
+            fn reply(self) -> Result<Value, RpcError> {
+                Ok::<_, SimulationError>(self).reply()
+            }
+        })+
+    };
+}
+
+infallible_reply!(SimulationHealth, String, u64);
+
+/// Calls the Tauri command with the same name as the method. Parameters are named
+/// like the `invoke` arguments of the frontend, in camelCase. With an app handle the
+/// commands get it as their first argument and any other method goes to `$otherwise`.
+macro_rules! dispatch {
+    ($app:expr, $method:expr, $params:expr, $otherwise:expr; $($command:path { $($arg:ident: $ty:ty),* }),+ $(,)?) => {
+        match $method {
+            $(m if m == command_name(stringify!($command)) => {
+                let ($($arg,)*) = dispatch!(@params $params; $($arg: $ty),*);
+                $command($app.clone(), $($arg),*).reply()
+            })+
+            _ => $otherwise,
+        }
+    };
+    ($method:expr, $params:expr; $($command:path { $($arg:ident: $ty:ty),* }),+ $(,)?) => {
+        match $method {
+            $(m if m == command_name(stringify!($command)) => {
+                let ($($arg,)*) = dispatch!(@params $params; $($arg: $ty),*);
+                $command($($arg),*).reply()
+            })+
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method '{}'", $method))),
+        }
+    };
+    (@params $params:expr; $($arg:ident: $ty:ty),*) => {{
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params {
+            $($arg: $ty,)*
+        }
+
+        let Params { $($arg,)* } = serde_json::from_value($params)
+            .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+        ($($arg,)*)
+    }};
+}
+
+/// `simulation :: stop_simulation` as written by `stringify!` is `stop_simulation`.
+fn command_name(path: &str) -> &str {
+    path.rsplit(':').next().unwrap_or(path).trim()
+}
+
+fn call(app: &AppHandle, method: &str, params: Value) -> Result<Value, RpcError> {
+    dispatch!(app, method, params, call_pure(method, params);
+        generic_process::create_process { name: String, resource_intensity: GenericProcessResourceIntensity },
+        generic_process::process_add_resource { process_id: String, resource_id: String, amount: u64 },
+        generic_process::process_remove_resource { process_id: String, resource_id: String },
+        generic_process::process_get_resource_intensity { process_id: String },
+        generic_process::process_set_name { process_id: String, name: String },
+        generic_process::process_get_name { process_id: String },
+        generic_process::process_set_resource_intensity { process_id: String, resource_intensity: GenericProcessResourceIntensity },
+        simulation::simulation_remove_process { process_id: String },
+        simulation::simulation_add_process { name: String, resource_intensity: GenericProcessResourceIntensity },
+        simulation::simulation_add_resource { resource: GenericResource },
+        simulation::simulation_remove_resource { resource_id: String, policy: Option<ResourceRemovalPolicy> },
+        simulation::simulation_processes {},
+        simulation::simulation_resources {},
+        simulation::simulation_set_resource_total_amount { resource_id: String, total_amount: u64 },
+        simulation::simulation_set_simulation_speed { speed: f64 },
+        simulation::simulation_speed {},
+        simulation::simulation_set_unbounded { unbounded: bool },
+        simulation::simulation_set_publish_rate { publish_rate: u64 },
+        simulation::simulation_tick_rate {},
+        simulation::simulation_time {},
+        simulation::simulation_set_time_step { time_step: f64 },
+        simulation::simulation_engine {},
+        simulation::simulation_set_engine { engine: Engine },
+        simulation::simulation_set_seed { seed: u64 },
+        simulation::stop_simulation {},
+        simulation::start_simulation {},
+        simulation::simulation_resync {},
+        simulation::simulation_health {},
+        simulation::simulation_recover {},
+        batch::simulation_apply_batch { mutations: Vec<Mutation> },
+        scenario::simulation_load_scenario { path: String },
+        scenario::simulation_save_scenario { path: String },
+        procfs::simulation_import_system { limit: Option<usize> },
+        monitor::system_monitor_start { interval_ms: Option<u64> },
+        monitor::system_monitor_stop {},
+        cgroup::simulation_import_cgroup { path: String },
+        save_state::simulation_save_state { path: String },
+        save_state::simulation_restore_state { path: String },
+        autosave::autosave_settings {},
+        autosave::autosave_set_settings { settings: AutosaveSettings },
+        autosave::autosave_list {},
+        autosave::autosave_restore { name: Option<String> },
+    )
+}
+
+/// The commands that only need their arguments.
+fn call_pure(method: &str, params: Value) -> Result<Value, RpcError> {
+    dispatch!(method, params;
+        generic_resource::create_resource { name: String, total_amount: u64, blocking: bool },
+        generic_resource::get_resource_name { resource: GenericResource },
+        generic_resource::set_resource_name { resource: GenericResource, name: String },
+        generic_resource::get_resource_total_amount { resource: GenericResource },
+        generic_resource::set_resource_total_amount { resource: GenericResource, total_amount: u64 },
+        generic_resource::get_resource_free_amount { resource: GenericResource },
+        locks::system_lock_report {},
+        cgroup::system_cgroup_report { path: String },
+    )
+}
+
+/// Outgoing messages of one client. Events are dropped instead of queued when the
+/// client stops reading, so a stuck script never holds up the simulation; the
+/// envelope sequence shows it what it missed.
+#[derive(Clone)]
+struct Connection(SyncSender<Value>);
+
+impl Connection {
+    fn reply(&self, message: Value) -> bool {
+        self.0.send(message).is_ok()
+    }
+
+    fn notify(&self, message: Value) {
+        let _ = self.0.try_send(message);
+    }
+}
+
+/// Answers one line. `call` runs the method; requests without an id are
+/// notifications and get no response.
+fn handle(line: &str, call: impl FnOnce(&str, Value) -> Result<Value, RpcError>) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e))),
+    };
+
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return Some(error_response(
+            id.unwrap_or(Value::Null),
+            RpcError::new(INVALID_REQUEST, "Missing method"),
+        ));
+    };
+    let params = match request.get("params") {
+        None | Some(Value::Null) => json!({}),
+        Some(params) => params.clone(),
+    };
+
+    let result = call(method, params);
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+/// `call` plus the methods that manage the event subscription of the client.
+fn call_or_subscribe(
+    app: &AppHandle,
+    method: &str,
+    params: Value,
+    connection: &Connection,
+    subscription: &mut Option<tauri::EventId>,
+) -> Result<Value, RpcError> {
+    match method {
+        // Forwards every event the webview receives as a `simulation` notification.
+        "subscribe" => {
+            if subscription.is_none() {
+                let connection = connection.clone();
+                *subscription = Some(app.listen_any(EVENT_CHANNEL, move |event| {
+                    let params = serde_json::from_str::<Value>(event.payload()).unwrap_or_default();
+                    connection.notify(json!({
+                        "jsonrpc": "2.0",
+                        "method": EVENT_CHANNEL,
+                        "params": params,
+                    }));
+                }));
+            }
+            Ok(Value::Null)
+        }
+        "unsubscribe" => {
+            if let Some(subscription) = subscription.take() {
+                app.unlisten(subscription);
+            }
+            Ok(Value::Null)
+        }
+        method => call(app, method, params),
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+fn serve(app: AppHandle, stream: UnixStream) -> std::io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    let (tx, rx) = sync_channel::<Value>(QUEUE_SIZE);
+    let connection = Connection(tx);
+
+    let mut writer = stream;
+    thread::spawn(move || {
+        for message in rx {
+            if writeln!(writer, "{}", message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscription = None;
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(&line, |method, params| {
+            call_or_subscribe(&app, method, params, &connection, &mut subscription)
+        });
+        if let Some(response) = response {
+            if !connection.reply(response) {
+                break;
+            }
+        }
+    }
+
+    // Also ends the writer once the listener drops its sender.
+    if let Some(subscription) = subscription {
+        app.unlisten(subscription);
+    }
+    Ok(())
+}
+
+/// Listens on `<app data>/control.sock` for JSON-RPC 2.0 requests, one per line,
+/// so scripts and test harnesses can drive the running app like the frontend does.
+pub fn start_control_socket(app: AppHandle) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = app.path().app_data_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(SOCKET_FILE);
+
+    // A socket left behind by a previous run would make the bind fail.
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    // Anyone who can connect can drive the simulation, so only the owner may.
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+
+            let app = app.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve(app, stream) {
+                    println!("Error: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(id: Option<Value>, method: &str, params: Value) -> String {
+        let mut request = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        if let Some(id) = id {
+            request["id"] = id;
+        }
+        request.to_string()
+    }
+
+    fn error_code(response: Option<Value>) -> i64 {
+        response.unwrap()["error"]["code"].as_i64().unwrap()
+    }
+
+    fn disk() -> Value {
+        json!({ "name": "Disk", "totalAmount": 10, "blocking": false })
+    }
+
+    #[test]
+    fn malformed_json_is_a_parse_error() {
+        let response = handle("{\"jsonrpc\": \"2.0\",", call_pure);
+        assert_eq!(response.as_ref().unwrap()["id"], Value::Null);
+        assert_eq!(error_code(response), PARSE_ERROR);
+    }
+
+    #[test]
+    fn unknown_methods_are_reported() {
+        let response = handle(
+            &line(Some(json!(1)), "simulation_fly", json!({})),
+            call_pure,
+        );
+        assert_eq!(response.as_ref().unwrap()["id"], 1);
+        assert_eq!(error_code(response), METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn notifications_get_no_reply() {
+        assert_eq!(
+            handle(&line(None, "simulation_fly", json!({})), call_pure),
+            None
+        );
+        assert_eq!(
+            handle(&line(None, "create_resource", disk()), call_pure),
+            None
+        );
+    }
+
+    #[test]
+    fn commands_without_the_app_are_called() {
+        let response = handle(
+            &line(Some(json!("a")), "create_resource", disk()),
+            call_pure,
+        );
+        let response = response.unwrap();
+        assert_eq!(response["id"], "a");
+        assert_eq!(response["result"]["name"], "Disk");
+
+        let missing = json!({ "name": "Disk" });
+        let response = handle(&line(Some(json!(2)), "create_resource", missing), call_pure);
+        assert_eq!(error_code(response), INVALID_PARAMS);
+
+        let blank = json!({ "name": " ", "totalAmount": 10, "blocking": false });
+        let response = handle(&line(Some(json!(3)), "create_resource", blank), call_pure);
+        assert_eq!(error_code(response), COMMAND_FAILED);
+    }
+}
@@ -0,0 +1,448 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashSet};
+
+use rand::Rng;
+
+use crate::events::{ProcessStateKind, SimulationEvent};
+use crate::generic_process::{AllProcessTraits, GenericProcessResourceIntensity, ProcessStates};
+use crate::generic_resource::GenericResource;
+
+/// How the worker advances the simulation.
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Debug, Default)]
+pub enum Engine {
+    /// Every tick polls every process and moves the clock by one time step.
+    #[default]
+    Ticked,
+    /// Every tick jumps the clock straight to the next scheduled event.
+    DiscreteEvent,
+}
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+enum EventKind {
+    /// The process enters the simulation.
+    Arrival,
+    /// The process rolls what it needs this time and asks for it.
+    Request,
+    /// The process gets its resources, or blocks until someone releases theirs.
+    Grant,
+    /// The process gives its resources back.
+    Release,
+    /// The process is done with this round and goes back to ready.
+    Finish,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+struct ScheduledEvent {
+    time: f64,
+    /// Keeps events with the same timestamp in the order they were scheduled.
+    sequence: u64,
+    process_id: String,
+    kind: EventKind,
+}
+
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+impl PartialOrd for ScheduledEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// `BinaryHeap` is a max-heap, so the earliest event has to compare as the greatest.
+impl Ord for ScheduledEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .time
+            .total_cmp(&self.time)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+/// Time until a ready process asks for resources again. Busier processes ask sooner.
+fn think_time(intensity: GenericProcessResourceIntensity, rng: &mut impl Rng) -> f64 {
+    let intensity = (intensity as u64).max(1) as f64;
+    rng.gen_range(1.0..=10.0) / intensity
+}
+
+/// Time a working process holds its resources.
+fn service_time(rng: &mut impl Rng) -> f64 {
+    rng.gen_range(1.0..=5.0)
+}
+
+/// Moves `process` to the `to` state if that is a valid transition, and returns
+/// the event describing it.
+fn transition(process: &mut ProcessStates, to: ProcessStateKind) -> Option<SimulationEvent> {
+    let from = ProcessStateKind::from(&*process);
+    let next = match (process.clone(), to) {
+        (ProcessStates::Ready(p), ProcessStateKind::Working) => ProcessStates::Working(p.run()),
+        (ProcessStates::Ready(p), ProcessStateKind::Blocked) => ProcessStates::Blocked(p.block()),
+        (ProcessStates::Blocked(p), ProcessStateKind::Ready) => ProcessStates::Ready(p.unblock()),
+        (ProcessStates::Working(p), ProcessStateKind::Ready) => ProcessStates::Ready(p.finish()),
+        _ => return None,
+    };
+
+    *process = next;
+    Some(SimulationEvent::StateTransition {
+        process_id: process.id(),
+        from,
+        to,
+    })
+}
+
+/// Every process waits in `Blocked` and none is working, so nobody will ever release
+/// what the others wait for.
+pub fn deadlocked(processes: &[ProcessStates]) -> bool {
+    !processes.is_empty()
+        && processes
+            .iter()
+            .all(|p| matches!(p, ProcessStates::Blocked(_)))
+}
+
+/// Discrete-event engine over the same processes and resources as the ticked one.
+///
+/// Unlike the ticked engine it really takes resources: a granted process holds its
+/// amounts until it releases them, and a process that cannot get a blocking resource
+/// waits in `Blocked` until some other process releases.
+///
+/// Only ordered collections, so saving the same engine twice writes the same bytes.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Default)]
+pub struct DiscreteEngine {
+    queue: BinaryHeap<ScheduledEvent>,
+    sequence: u64,
+    /// Processes that already had their arrival scheduled.
+    known: BTreeSet<String>,
+    /// Amounts each working process holds, as (resource id, amount).
+    held: BTreeMap<String, Vec<(String, u64)>>,
+}
+
+impl DiscreteEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn schedule(&mut self, time: f64, process_id: String, kind: EventKind) {
+        self.queue.push(ScheduledEvent {
+            time,
+            sequence: self.sequence,
+            process_id,
+            kind,
+        });
+        self.sequence += 1;
+    }
+
+    fn release(&mut self, process_id: &str, resources: &mut [GenericResource]) {
+        for (resource_id, amount) in self.held.remove(process_id).unwrap_or_default() {
+            if let Some(resource) = resources.iter_mut().find(|r| r.id() == resource_id) {
+                let free = (resource.free_amount() + amount).min(resource.total_amount());
+                resource.set_free_amount(free);
+            }
+        }
+    }
+
+    /// Picks up processes added or removed by commands since the last step.
+    fn sync(&mut self, now: f64, processes: &[ProcessStates], resources: &mut [GenericResource]) {
+        let ids: HashSet<String> = processes.iter().map(|p| p.id()).collect();
+
+        let gone: Vec<String> = self
+            .held
+            .keys()
+            .filter(|id| !ids.contains(*id))
+            .cloned()
+            .collect();
+        for id in gone {
+            self.release(&id, resources);
+        }
+        self.known.retain(|id| ids.contains(id));
+
+        for process in processes {
+            if self.known.insert(process.id()) {
+                self.schedule(now, process.id(), EventKind::Arrival);
+            }
+        }
+    }
+
+    /// Time of the next event, or `None` when nothing is scheduled.
+    pub fn next_time(
+        &mut self,
+        now: f64,
+        processes: &[ProcessStates],
+        resources: &mut [GenericResource],
+    ) -> Option<f64> {
+        self.sync(now, processes, resources);
+        self.queue.peek().map(|event| event.time.max(now))
+    }
+
+    /// Handles every event scheduled up to `now`, including the ones they schedule
+    /// for the same instant, and returns what the frontend should hear about.
+    pub fn step(
+        &mut self,
+        now: f64,
+        processes: &mut [ProcessStates],
+        resources: &mut [GenericResource],
+        rng: &mut impl Rng,
+    ) -> Vec<SimulationEvent> {
+        let mut events = vec![];
+
+        while self.queue.peek().is_some_and(|event| event.time <= now) {
+            let Some(event) = self.queue.pop() else {
+                break;
+            };
+            // The process may have been removed after the event was scheduled.
+            let Some(index) = processes.iter().position(|p| p.id() == event.process_id) else {
+                continue;
+            };
+
+            match event.kind {
+                EventKind::Arrival => {
+                    let intensity = *processes[index].process().resource_intensity();
+                    self.schedule(
+                        now + think_time(intensity, rng),
+                        event.process_id,
+                        EventKind::Request,
+                    );
+                }
+                EventKind::Request => {
+                    if matches!(processes[index], ProcessStates::Working(_)) {
+                        continue;
+                    }
+                    events.extend(transition(&mut processes[index], ProcessStateKind::Ready));
+
+                    let ProcessStates::Ready(process) = &mut processes[index] else {
+                        continue;
+                    };
+                    if *process.resource_intensity() == GenericProcessResourceIntensity::None {
+                        let wait = think_time(GenericProcessResourceIntensity::None, rng);
+                        self.schedule(now + wait, event.process_id, EventKind::Request);
+                        continue;
+                    }
+
+                    process.prepare(rng);
+                    self.schedule(now, event.process_id, EventKind::Grant);
+                }
+                EventKind::Grant => {
+                    let ProcessStates::Ready(process) = &processes[index] else {
+                        continue;
+                    };
+
+                    let mut wanted = vec![];
+                    let mut available = true;
+                    for slot in process.resource_slot() {
+                        let Some(resource) =
+                            resources.iter().find(|r| r.id() == slot.resource_id())
+                        else {
+                            continue;
+                        };
+
+                        let amount = if resource.blocking() {
+                            slot.current_amount().min(resource.total_amount())
+                        } else {
+                            slot.current_amount().min(resource.free_amount())
+                        };
+                        available &= amount <= resource.free_amount();
+                        wanted.push((slot.id(), slot.resource_id(), amount));
+                    }
+
+                    if !available {
+                        events.extend(transition(&mut processes[index], ProcessStateKind::Blocked));
+                        continue;
+                    }
+
+                    let mut held = vec![];
+                    for (slot_id, resource_id, amount) in wanted {
+                        if let Some(resource) = resources.iter_mut().find(|r| r.id() == resource_id)
+                        {
+                            resource.set_free_amount(resource.free_amount() - amount);
+                        }
+                        events.push(SimulationEvent::Allocation {
+                            process_id: event.process_id.clone(),
+                            slot_id,
+                            resource_id: resource_id.clone(),
+                            amount,
+                        });
+                        held.push((resource_id, amount));
+                    }
+                    self.held.insert(event.process_id.clone(), held);
+
+                    events.extend(transition(&mut processes[index], ProcessStateKind::Working));
+                    self.schedule(
+                        now + service_time(rng),
+                        event.process_id,
+                        EventKind::Release,
+                    );
+                }
+                EventKind::Release => {
+                    if !matches!(processes[index], ProcessStates::Working(_)) {
+                        continue;
+                    }
+
+                    self.release(&event.process_id, resources);
+                    self.schedule(now, event.process_id, EventKind::Finish);
+
+                    // Everyone waiting gets another chance at what was just freed.
+                    let blocked: Vec<String> = processes
+                        .iter()
+                        .filter(|p| matches!(p, ProcessStates::Blocked(_)))
+                        .map(|p| p.id())
+                        .collect();
+                    for process_id in blocked {
+                        self.schedule(now, process_id, EventKind::Request);
+                    }
+                }
+                EventKind::Finish => {
+                    events.extend(transition(&mut processes[index], ProcessStateKind::Ready));
+
+                    let intensity = *processes[index].process().resource_intensity();
+                    self.schedule(
+                        now + think_time(intensity, rng),
+                        event.process_id,
+                        EventKind::Request,
+                    );
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Gives back everything held, puts every process back to ready and forgets the
+    /// schedule, so either engine can take over from a clean state.
+    pub fn reset(&mut self, processes: &mut [ProcessStates], resources: &mut [GenericResource]) {
+        let holders: Vec<String> = self.held.keys().cloned().collect();
+        for process_id in holders {
+            self.release(&process_id, resources);
+        }
+
+        for process in processes.iter_mut() {
+            transition(process, ProcessStateKind::Ready);
+        }
+
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic_process::Process;
+    use crate::simulation::SimulationRng;
+    use rand::SeedableRng;
+
+    /// Two processes that each want 2 of a blocking resource of 3, so only one of them
+    /// fits at a time. Low intensity rolls a new amount on every request, 2 to 2.99
+    /// times the base of 1.
+    fn contended() -> (Vec<ProcessStates>, Vec<GenericResource>) {
+        let resource = GenericResource::new("Lock".to_string(), 3, true);
+        let processes = (0..2)
+            .map(|i| {
+                let mut process = Process::new(
+                    format!("Process {}", i),
+                    GenericProcessResourceIntensity::Low,
+                );
+                process.add_resource(&resource, 1);
+                ProcessStates::Ready(process)
+            })
+            .collect();
+        (processes, vec![resource])
+    }
+
+    #[test]
+    fn processes_wait_for_what_others_hold() {
+        let (mut processes, mut resources) = contended();
+        let mut engine = DiscreteEngine::new();
+        let mut rng = SimulationRng::seed_from_u64(7);
+        let mut events = vec![];
+        let mut now = 0.0;
+
+        for _ in 0..200 {
+            let next = engine
+                .next_time(now, &processes, &mut resources)
+                .expect("contended processes never run out of events");
+            assert!(next >= now);
+            now = next;
+            events.extend(engine.step(now, &mut processes, &mut resources, &mut rng));
+
+            let working = processes
+                .iter()
+                .filter(|p| matches!(p, ProcessStates::Working(_)))
+                .count();
+            let held: u64 = engine.held.values().flatten().map(|(_, a)| a).sum();
+            assert!(working <= 1);
+            assert_eq!(resources[0].free_amount() + held, 3);
+        }
+
+        let moved_to = |to: ProcessStateKind| {
+            events
+                .iter()
+                .any(|e| matches!(e, SimulationEvent::StateTransition { to: t, .. } if *t == to))
+        };
+        assert!(moved_to(ProcessStateKind::Blocked));
+        for process in &processes {
+            assert!(events.iter().any(|e| matches!(
+                e,
+                SimulationEvent::StateTransition {
+                    process_id,
+                    to: ProcessStateKind::Working,
+                    ..
+                } if *process_id == process.id()
+            )));
+        }
+    }
+
+    #[test]
+    fn reset_gives_everything_back() {
+        let (mut processes, mut resources) = contended();
+        let mut engine = DiscreteEngine::new();
+        let mut rng = SimulationRng::seed_from_u64(7);
+        let mut now = 0.0;
+        while engine.held.is_empty() {
+            now = engine.next_time(now, &processes, &mut resources).unwrap();
+            engine.step(now, &mut processes, &mut resources, &mut rng);
+        }
+
+        engine.reset(&mut processes, &mut resources);
+        assert_eq!(resources[0].free_amount(), 3);
+        assert!(processes
+            .iter()
+            .all(|p| matches!(p, ProcessStates::Ready(_))));
+        assert!(engine.queue.is_empty());
+    }
+
+    #[test]
+    fn removed_processes_give_back_what_they_hold() {
+        let (mut processes, mut resources) = contended();
+        let mut engine = DiscreteEngine::new();
+        let mut rng = SimulationRng::seed_from_u64(7);
+        let mut now = 0.0;
+        while engine.held.is_empty() {
+            now = engine.next_time(now, &processes, &mut resources).unwrap();
+            engine.step(now, &mut processes, &mut resources, &mut rng);
+        }
+
+        processes.retain(|p| !matches!(p, ProcessStates::Working(_)));
+        engine.next_time(now, &processes, &mut resources);
+        assert_eq!(resources[0].free_amount(), 3);
+        assert!(engine.held.is_empty());
+    }
+
+    #[test]
+    fn deadlocks_need_every_process_blocked() {
+        let (processes, _) = contended();
+        assert!(!deadlocked(&processes));
+        assert!(!deadlocked(&[]));
+
+        let blocked: Vec<ProcessStates> = processes
+            .into_iter()
+            .map(|p| match p {
+                ProcessStates::Ready(p) => ProcessStates::Blocked(p.block()),
+                p => p,
+            })
+            .collect();
+        assert!(deadlocked(&blocked));
+    }
+}
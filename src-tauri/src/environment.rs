@@ -0,0 +1,489 @@
+use std::fmt;
+
+use rand::{Rng, SeedableRng};
+
+use crate::generic_process::{AllProcessTraits, Process, ProcessStates, ReadyProcess};
+use crate::generic_resource::GenericResource;
+use crate::scenario::Scenario;
+use crate::simulation::{safe_to_continue, SimulationError, SimulationRng};
+
+/// Reward for every request granted.
+pub const GRANT_REWARD: f64 = 1.0;
+/// Reward for every process that gets everything it needs and finishes.
+pub const FINISH_REWARD: f64 = 10.0;
+/// Penalty for granting a request that does not fit in what is free.
+pub const INVALID_GRANT_PENALTY: f64 = -1.0;
+/// Penalty for every step, so denying everything forever is not free.
+pub const STEP_PENALTY: f64 = -0.1;
+/// Penalty for ending the episode in a deadlock.
+pub const DEADLOCK_PENALTY: f64 = -100.0;
+
+pub const DEFAULT_MAX_STEPS: u64 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvironmentError {
+    ActionLength { expected: usize, got: usize },
+    EpisodeOver,
+}
+
+impl fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::ActionLength { expected, got } => write!(
+                f,
+                "The action has {} entries but there are {} processes",
+                got, expected
+            ),
+            EnvironmentError::EpisodeOver => {
+                write!(f, "The episode is over, call reset to start another")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EnvironmentError {}
+
+impl serde::Serialize for EnvironmentError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// How an episode ended.
+#[derive(Clone, Copy, PartialEq, serde::Serialize, Debug)]
+pub enum Outcome {
+    /// Every process got what it needed and finished.
+    Completed,
+    /// No pending request fits in what is free, so nothing can move again.
+    Deadlock,
+    /// The episode ran out of steps.
+    TimeLimit,
+}
+
+/// What the allocator sees. Rows are processes and columns resources, in the order
+/// of `Environment::processes` and `Environment::resources`.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct Observation {
+    /// Amounts each process holds.
+    pub allocation: Vec<Vec<u64>>,
+    /// Amounts each process still needs before it can finish.
+    pub need: Vec<Vec<u64>>,
+    /// Amounts each process asks for right now, zero when it is not asking.
+    pub requests: Vec<Vec<u64>>,
+    pub free: Vec<u64>,
+    pub finished: Vec<bool>,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct StepResult {
+    pub observation: Observation,
+    pub reward: f64,
+    /// The episode ended on its own, by completing or deadlocking.
+    pub terminated: bool,
+    /// The episode was cut off by the step limit.
+    pub truncated: bool,
+    pub outcome: Option<Outcome>,
+}
+
+/// Reinforcement-learning style episodes over the processes and resources of a scenario.
+///
+/// On `reset` every process rolls how much of each resource it needs, the same way it
+/// does in the simulation, and then asks for it in chunks. Each `step` the allocator
+/// grants or denies every pending request. A process that gets everything it needs
+/// finishes and gives it all back.
+pub struct Environment {
+    resources: Vec<GenericResource>,
+    processes: Vec<ReadyProcess>,
+    max_steps: u64,
+    rng: SimulationRng,
+    allocation: Vec<Vec<u64>>,
+    need: Vec<Vec<u64>>,
+    requests: Vec<Option<Vec<u64>>>,
+    free: Vec<u64>,
+    steps: u64,
+    done: bool,
+}
+
+impl Environment {
+    /// Only ready processes take part, since the others are already in the middle of
+    /// something.
+    pub fn new(
+        resources: Vec<GenericResource>,
+        processes: Vec<ProcessStates>,
+        max_steps: u64,
+    ) -> Self {
+        let processes = processes
+            .into_iter()
+            .filter_map(|p| match p {
+                ProcessStates::Ready(process) => Some(process),
+                _ => None,
+            })
+            .collect();
+
+        Environment {
+            resources,
+            processes,
+            max_steps,
+            rng: SimulationRng::seed_from_u64(0),
+            allocation: vec![],
+            need: vec![],
+            requests: vec![],
+            free: vec![],
+            steps: 0,
+            done: true,
+        }
+    }
+
+    pub fn from_scenario(scenario: &Scenario, max_steps: u64) -> Result<Self, SimulationError> {
+        let (resources, processes) = scenario.build()?;
+        Ok(Self::new(resources, processes, max_steps))
+    }
+
+    pub fn processes(&self) -> Vec<String> {
+        self.processes.iter().map(|p| p.name()).collect()
+    }
+
+    pub fn resources(&self) -> Vec<String> {
+        self.resources.iter().map(|r| r.name()).collect()
+    }
+
+    /// Starts a new episode. The same seed always plays out the same way for the same
+    /// actions.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.rng = SimulationRng::seed_from_u64(seed);
+        self.free = self.resources.iter().map(|r| r.total_amount()).collect();
+
+        // Needs are capped at the total, or the episode could never complete.
+        let mut need = vec![];
+        for process in &self.processes {
+            let mut process = process.clone();
+            process.prepare(&mut self.rng);
+            need.push(
+                self.resources
+                    .iter()
+                    .zip(&self.free)
+                    .map(|(resource, total)| {
+                        process
+                            .resource_slot()
+                            .iter()
+                            .filter(|s| s.resource_id() == resource.id())
+                            .map(|s| s.current_amount())
+                            .sum::<u64>()
+                            .min(*total)
+                    })
+                    .collect(),
+            );
+        }
+
+        self.need = need;
+        self.allocation = vec![vec![0; self.resources.len()]; self.processes.len()];
+        self.requests = vec![None; self.processes.len()];
+        self.steps = 0;
+        self.done = false;
+        self.ask();
+
+        self.observation()
+    }
+
+    /// `action` has one entry per process: true grants its pending request, false
+    /// leaves it waiting. Entries for processes that are not asking are ignored.
+    pub fn step(&mut self, action: &[bool]) -> Result<StepResult, EnvironmentError> {
+        if self.done {
+            return Err(EnvironmentError::EpisodeOver);
+        }
+        if action.len() != self.processes.len() {
+            return Err(EnvironmentError::ActionLength {
+                expected: self.processes.len(),
+                got: action.len(),
+            });
+        }
+
+        let mut reward = STEP_PENALTY;
+        for (i, grant) in action.iter().enumerate() {
+            if !grant {
+                continue;
+            }
+            let Some(request) = self.requests[i].clone() else {
+                continue;
+            };
+
+            if request
+                .iter()
+                .zip(&self.free)
+                .any(|(amount, free)| amount > free)
+            {
+                reward += INVALID_GRANT_PENALTY;
+                continue;
+            }
+
+            for (j, amount) in request.iter().enumerate() {
+                self.free[j] -= amount;
+                self.allocation[i][j] += amount;
+                self.need[i][j] -= amount;
+            }
+            self.requests[i] = None;
+            reward += GRANT_REWARD;
+
+            if self.need[i].iter().all(|n| *n == 0) {
+                for (j, amount) in self.allocation[i].iter_mut().enumerate() {
+                    self.free[j] += *amount;
+                    *amount = 0;
+                }
+                reward += FINISH_REWARD;
+            }
+        }
+
+        self.ask();
+        self.steps += 1;
+
+        let outcome = if self.finished().iter().all(|f| *f) {
+            Some(Outcome::Completed)
+        } else if self.deadlocked() {
+            reward += DEADLOCK_PENALTY;
+            Some(Outcome::Deadlock)
+        } else if self.steps >= self.max_steps {
+            Some(Outcome::TimeLimit)
+        } else {
+            None
+        };
+        self.done = outcome.is_some();
+
+        Ok(StepResult {
+            observation: self.observation(),
+            reward,
+            terminated: matches!(outcome, Some(Outcome::Completed | Outcome::Deadlock)),
+            truncated: outcome == Some(Outcome::TimeLimit),
+            outcome,
+        })
+    }
+
+    /// The allocator to beat: grants a request only if `safe_to_continue` still holds
+    /// afterwards, treating what is free as the total and what is left as the request.
+    /// That check never counts what finishing processes give back, so it is cautious
+    /// and can stall until the step limit.
+    pub fn baseline_action(&self) -> Vec<bool> {
+        let mut free = self.free.clone();
+        let mut need = self.need.clone();
+        let mut action = vec![false; self.processes.len()];
+
+        for (i, request) in self.requests.iter().enumerate() {
+            let Some(request) = request else {
+                continue;
+            };
+            if request
+                .iter()
+                .zip(&free)
+                .any(|(amount, free)| amount > free)
+            {
+                continue;
+            }
+
+            let mut next_free = free.clone();
+            let mut next_need = need.clone();
+            for (j, amount) in request.iter().enumerate() {
+                next_free[j] -= amount;
+                next_need[i][j] -= amount;
+            }
+            // A process that finishes gives everything back.
+            if next_need[i].iter().all(|n| *n == 0) {
+                for (j, amount) in self.allocation[i].iter().enumerate() {
+                    next_free[j] += amount + request[j];
+                }
+            }
+
+            if self.safe(&next_need, &next_free) {
+                action[i] = true;
+                free = next_free;
+                need = next_need;
+            }
+        }
+
+        action
+    }
+
+    fn safe(&self, need: &[Vec<u64>], free: &[u64]) -> bool {
+        let resources: Vec<GenericResource> = self
+            .resources
+            .iter()
+            .zip(free)
+            .map(|(r, free)| GenericResource::new(r.name(), *free, r.blocking()))
+            .collect();
+
+        let processes = self
+            .processes
+            .iter()
+            .zip(need)
+            .filter(|(_, need)| need.iter().any(|n| *n > 0))
+            .map(|(p, need)| {
+                let mut process = Process::new(p.name(), *p.resource_intensity());
+                for (resource, amount) in resources.iter().zip(need) {
+                    process.add_resource(resource, *amount);
+                }
+                ProcessStates::Ready(process)
+            })
+            .collect();
+
+        safe_to_continue(processes, resources)
+    }
+
+    /// Every unfinished process without a pending request asks for part of what it
+    /// still needs.
+    fn ask(&mut self) {
+        for i in 0..self.processes.len() {
+            if self.requests[i].is_some() || self.need[i].iter().all(|n| *n == 0) {
+                continue;
+            }
+
+            let request = self.need[i]
+                .iter()
+                .map(|need| match need {
+                    0 => 0,
+                    need => self.rng.gen_range(1..=*need),
+                })
+                .collect();
+            self.requests[i] = Some(request);
+        }
+    }
+
+    fn finished(&self) -> Vec<bool> {
+        self.need
+            .iter()
+            .map(|need| need.iter().all(|n| *n == 0))
+            .collect()
+    }
+
+    fn deadlocked(&self) -> bool {
+        self.requests.iter().flatten().all(|request| {
+            request
+                .iter()
+                .zip(&self.free)
+                .any(|(amount, free)| amount > free)
+        })
+    }
+
+    fn observation(&self) -> Observation {
+        Observation {
+            allocation: self.allocation.clone(),
+            need: self.need.clone(),
+            requests: self
+                .requests
+                .iter()
+                .map(|r| r.clone().unwrap_or(vec![0; self.resources.len()]))
+                .collect(),
+            free: self.free.clone(),
+            finished: self.finished(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic_process::GenericProcessResourceIntensity;
+
+    /// Two processes needing at most 3 of 10 each, so granting everything completes.
+    fn environment(max_steps: u64) -> Environment {
+        let resource = GenericResource::new("Memory".to_string(), 10, false);
+        let processes = (0..2)
+            .map(|i| {
+                let mut process = Process::new(
+                    format!("Process {}", i),
+                    GenericProcessResourceIntensity::Low,
+                );
+                process.add_resource(&resource, 1);
+                ProcessStates::Ready(process)
+            })
+            .collect();
+        Environment::new(vec![resource], processes, max_steps)
+    }
+
+    fn play(environment: &mut Environment, seed: u64, grant: bool) -> Vec<StepResult> {
+        environment.reset(seed);
+        let mut results = vec![];
+        loop {
+            let result = environment.step(&[grant, grant]).unwrap();
+            let done = result.terminated || result.truncated;
+            results.push(result);
+            if done {
+                return results;
+            }
+        }
+    }
+
+    #[test]
+    fn steps_need_a_running_episode_and_one_entry_per_process() {
+        let mut environment = environment(10);
+        assert_eq!(
+            environment.step(&[true, true]).unwrap_err(),
+            EnvironmentError::EpisodeOver
+        );
+
+        environment.reset(0);
+        assert_eq!(
+            environment.step(&[true]).unwrap_err(),
+            EnvironmentError::ActionLength {
+                expected: 2,
+                got: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn granting_everything_that_fits_completes() {
+        let mut environment = environment(100);
+        let results = play(&mut environment, 1, true);
+
+        let last = results.last().unwrap();
+        assert_eq!(last.outcome, Some(Outcome::Completed));
+        assert!(last.terminated);
+        assert!(last.observation.finished.iter().all(|f| *f));
+        assert_eq!(last.observation.free, vec![10]);
+        for result in &results {
+            let held: u64 = result.observation.allocation.iter().flatten().sum();
+            assert_eq!(result.observation.free[0] + held, 10);
+        }
+    }
+
+    #[test]
+    fn denying_everything_runs_into_the_step_limit() {
+        let mut environment = environment(5);
+        let results = play(&mut environment, 1, false);
+
+        assert_eq!(results.len(), 5);
+        let last = results.last().unwrap();
+        assert_eq!(last.outcome, Some(Outcome::TimeLimit));
+        assert!(last.truncated && !last.terminated);
+        assert!(results.iter().all(|r| r.reward == STEP_PENALTY));
+        assert_eq!(
+            environment.step(&[false, false]).unwrap_err(),
+            EnvironmentError::EpisodeOver
+        );
+    }
+
+    #[test]
+    fn the_same_seed_plays_out_the_same_way() {
+        let mut environment = environment(100);
+        assert_eq!(
+            play(&mut environment, 9, true),
+            play(&mut environment, 9, true)
+        );
+    }
+
+    #[test]
+    fn requests_that_can_never_fit_end_in_a_deadlock() {
+        let mut environment = environment(100);
+        environment.reset(0);
+        environment.free = vec![1];
+        environment.need = vec![vec![2], vec![2]];
+        environment.requests = vec![Some(vec![2]), Some(vec![2])];
+
+        let result = environment.step(&[false, false]).unwrap();
+        assert_eq!(result.outcome, Some(Outcome::Deadlock));
+        assert!(result.terminated);
+        assert_eq!(result.reward, STEP_PENALTY + DEADLOCK_PENALTY);
+        assert!(!environment.baseline_action().iter().any(|grant| *grant));
+    }
+}
@@ -0,0 +1,185 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+
+#[cfg(feature = "desktop")]
+use tauri::{AppHandle, Emitter};
+
+use crate::clock::{SimulationTime, VirtualClock};
+
+use crate::generic_process::ProcessStates;
+use crate::monitor::SystemSample;
+use crate::simulation::SimulationHealth;
+use crate::snapshot::{Snapshot, SnapshotDelta};
+
+/// Every simulation event goes out on this single channel.
+pub const EVENT_CHANNEL: &str = "simulation";
+
+#[derive(Clone, Copy, PartialEq, serde::Serialize, Debug)]
+pub enum ProcessStateKind {
+    Ready,
+    Blocked,
+    Working,
+}
+
+impl From<&ProcessStates> for ProcessStateKind {
+    fn from(process: &ProcessStates) -> Self {
+        match process {
+            ProcessStates::Ready(_) => ProcessStateKind::Ready,
+            ProcessStates::Blocked(_) => ProcessStateKind::Blocked,
+            ProcessStates::Working(_) => ProcessStateKind::Working,
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+#[serde(tag = "type", content = "data")]
+pub enum SimulationEvent {
+    Keyframe(Snapshot),
+    Delta(SnapshotDelta),
+    StateTransition {
+        process_id: String,
+        from: ProcessStateKind,
+        to: ProcessStateKind,
+    },
+    Allocation {
+        process_id: String,
+        slot_id: String,
+        resource_id: String,
+        amount: u64,
+    },
+    UnsafeState {
+        process_ids: Vec<String>,
+    },
+    WorkerDied {
+        reason: String,
+    },
+    Recovered(SimulationHealth),
+    Started,
+    Stopped,
+    SpeedChanged {
+        speed: f64,
+    },
+    TickRate {
+        ticks_per_second: f64,
+    },
+    SystemSample(SystemSample),
+    MonitorFailed {
+        reason: String,
+    },
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct EventEnvelope {
+    /// Increases by one per event, so a gap means the consumer missed something.
+    sequence: u64,
+    time: SimulationTime,
+    event: SimulationEvent,
+}
+
+impl EventEnvelope {
+    pub fn event(&self) -> &SimulationEvent {
+        &self.event
+    }
+}
+
+/// Where emitted events end up: the webview in the app, anything else when the
+/// simulation runs headless.
+pub trait EventSink: Clone + Send + 'static {
+    fn send(&self, envelope: EventEnvelope);
+}
+
+#[cfg(feature = "desktop")]
+impl EventSink for AppHandle {
+    fn send(&self, envelope: EventEnvelope) {
+        if let Err(e) = self.emit::<EventEnvelope>(EVENT_CHANNEL, envelope) {
+            println!("Error: {}", e);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    sequence: Arc<AtomicU64>,
+    /// Stamps every event with the simulated time. Never held while emitting,
+    /// so the clock must not be locked by the caller either.
+    clock: Arc<Mutex<VirtualClock>>,
+}
+
+impl EventBus {
+    pub fn new(clock: Arc<Mutex<VirtualClock>>) -> Self {
+        Self {
+            sequence: Arc::new(AtomicU64::new(0)),
+            clock,
+        }
+    }
+
+    pub fn emit(&self, sink: &impl EventSink, event: SimulationEvent) {
+        let time = match self.clock.lock() {
+            Ok(clock) => clock.now(),
+            Err(poisoned) => poisoned.into_inner().now(),
+        };
+        let envelope = EventEnvelope {
+            sequence: self.sequence.fetch_add(1, Ordering::SeqCst),
+            time,
+            event,
+        };
+
+        sink.send(envelope);
+    }
+}
+
+/// Keeps every event it is sent, for tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct Recorder(Arc<Mutex<Vec<EventEnvelope>>>);
+
+#[cfg(test)]
+impl Recorder {
+    pub(crate) fn events(&self) -> Vec<SimulationEvent> {
+        let envelopes = self.0.lock().unwrap();
+        envelopes.iter().map(|e| e.event.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+impl EventSink for Recorder {
+    fn send(&self, envelope: EventEnvelope) {
+        self.0.lock().unwrap().push(envelope);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_numbered_and_stamped_with_the_clock() {
+        let clock = Arc::new(Mutex::new(VirtualClock::new()));
+        clock.lock().unwrap().set_time_step(0.5);
+        let bus = EventBus::new(Arc::clone(&clock));
+        let sink = Recorder::default();
+
+        let mut times = vec![];
+        for _ in 0..4 {
+            times.push(clock.lock().unwrap().advance());
+            bus.emit(&sink, SimulationEvent::Started);
+            bus.emit(&sink, SimulationEvent::Stopped);
+        }
+
+        let envelopes = sink.0.lock().unwrap();
+        assert_eq!(envelopes.len(), 8);
+        for (index, envelope) in envelopes.iter().enumerate() {
+            assert_eq!(envelope.sequence, index as u64);
+            assert_eq!(envelope.time, times[index / 2]);
+        }
+        assert_eq!(envelopes[7].time.time(), 2.0);
+
+        drop(envelopes);
+
+        // Clones share the counter, so events from every thread form one sequence.
+        bus.clone().emit(&sink, SimulationEvent::Started);
+        assert_eq!(sink.0.lock().unwrap()[8].sequence, 8);
+    }
+}
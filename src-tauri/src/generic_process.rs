@@ -2,10 +2,14 @@
 
 use nanoid::nanoid;
 use rand::Rng;
-use std::{marker::PhantomData, os::windows::process, sync::Mutex};
+use std::{marker::PhantomData, sync::Mutex};
+#[cfg(feature = "desktop")]
 use tauri::{Manager, State};
 
-use crate::{AllSimulationTrait, GenericResource, Simulation, TauriSim};
+use crate::validation::{validate_name, validate_slot};
+#[cfg(feature = "desktop")]
+use crate::TauriSim;
+use crate::{lock_state, AllSimulationTrait, GenericResource, Simulation, SimulationError};
 
 #[non_exhaustive]
 #[derive(Clone, PartialEq, Copy, serde::Serialize, serde::Deserialize, Debug)]
@@ -17,7 +21,7 @@ pub enum GenericProcessResourceIntensity {
     Extreme = 4,
 }
 
-#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
 pub struct ResourceSlot {
     resource_id: String,
     id: String,
@@ -57,7 +61,7 @@ pub struct Process {
     _marker: PhantomData<()>,
 }
 
-#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
 pub struct ReadyProcess {
     name: String,
     id: String,
@@ -65,7 +69,7 @@ pub struct ReadyProcess {
     resource_slot: Vec<ResourceSlot>,
 }
 
-#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
 pub struct BlockedProcess {
     name: String,
     id: String,
@@ -73,7 +77,7 @@ pub struct BlockedProcess {
     resource_slot: Vec<ResourceSlot>,
 }
 
-#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
 pub struct WorkingProcess {
     name: String,
     id: String,
@@ -82,14 +86,15 @@ pub struct WorkingProcess {
 }
 
 impl ReadyProcess {
-    pub fn prepare(&mut self) -> &Self {
+    /// Rolls how much of every slot this process wants now. Takes the simulation's
+    /// rng so a restored simulation rolls the same amounts.
+    pub fn prepare(&mut self, rng: &mut impl Rng) -> &Self {
         if self.resource_intensity == GenericProcessResourceIntensity::None {
             return self;
         }
 
         let intensity = self.resource_intensity as u64;
         for resource_slot in self.resource_slot_mut().iter_mut() {
-            let mut rng = rand::thread_rng();
             let roll = (rng.gen::<u64>() % 4) + 1;
 
             if roll >= intensity {
@@ -297,106 +302,133 @@ macro_rules! impl_AllProcessTraits {
 
 impl_AllProcessTraits!(for ReadyProcess, BlockedProcess, WorkingProcess);
 
-#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
 pub enum ProcessStates {
     Ready(ReadyProcess),
     Blocked(BlockedProcess),
     Working(WorkingProcess),
 }
 
+impl ProcessStates {
+    pub fn process(&self) -> &dyn AllProcessTraits {
+        match self {
+            ProcessStates::Ready(process) => process,
+            ProcessStates::Blocked(process) => process,
+            ProcessStates::Working(process) => process,
+        }
+    }
+
+    pub fn process_mut(&mut self) -> &mut dyn AllProcessTraits {
+        match self {
+            ProcessStates::Ready(process) => process,
+            ProcessStates::Blocked(process) => process,
+            ProcessStates::Working(process) => process,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self.process().id()
+    }
+}
+
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn create_process(
     app_handle: tauri::AppHandle,
     name: String,
     resource_intensity: GenericProcessResourceIntensity,
-) -> ReadyProcess {
+) -> Result<ReadyProcess, SimulationError> {
+    validate_name(&name)?;
+
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
     let proceses = sim.0.processes();
 
     let new_process = Process::new(name, resource_intensity);
-    proceses
-        .lock()
-        .unwrap()
-        .push(ProcessStates::Ready(new_process.clone()));
+    lock_state(&proceses)?.push(ProcessStates::Ready(new_process.clone()));
+    sim.0.wake();
 
-    new_process
+    Ok(new_process)
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn process_add_resource(
     app_handle: tauri::AppHandle,
     process_id: String,
     resource_id: String,
     amount: u64,
-) -> Result<(), String> {
+) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
 
     let processes = sim.0.processes();
     let resources = sim.0.resources();
 
-    let mut binding = processes.lock().unwrap();
-    let process = binding.iter_mut().find(|p| match p {
-        ProcessStates::Ready(process) => process.id == process_id,
-        ProcessStates::Blocked(process) => process.id == process_id,
-        ProcessStates::Working(process) => process.id == process_id,
-    });
-
-    let binding = resources.lock().unwrap();
-    let resource = binding.iter().find(|r| r.id() == resource_id);
+    // Resources before processes, see the lock order on `RunningSimulation`.
+    let resources = lock_state(&resources)?;
+    let mut processes = lock_state(&processes)?;
 
-    if process.is_none() {
-        return Err("Process not found".to_string());
-    }
+    let process = processes
+        .iter_mut()
+        .find(|p| p.id() == process_id)
+        .ok_or(SimulationError::ProcessNotFound)?;
 
-    if resource.is_none() {
-        return Err("Resource not found".to_string());
-    }
+    let resource = resources
+        .iter()
+        .find(|r| r.id() == resource_id)
+        .ok_or(SimulationError::ResourceNotFound)?;
 
-    let process = process.unwrap();
-    let resource = resource.unwrap();
+    validate_slot(process.process(), resource, amount)?;
+    process.process_mut().add_resource(resource, amount);
+    sim.0.wake();
 
-    match process {
-        ProcessStates::Ready(process) => {
-            process.add_resource(resource, amount);
-        }
-        ProcessStates::Blocked(process) => {
-            process.add_resource(resource, amount);
-        }
-        ProcessStates::Working(process) => {
-            process.add_resource(resource, amount);
-        }
-    }
     Ok(())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn process_remove_resource(
     app_handle: tauri::AppHandle,
+    process_id: String,
     resource_id: String,
-) -> Result<(), String> {
+) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let mut sim = state.lock().unwrap();
-    let running_binding = sim.0.resources();
-    let resources = running_binding.lock().unwrap();
+    let sim = lock_state(&state)?;
+    let processes = sim.0.processes();
+    let mut binding = lock_state(&processes)?;
+    remove_process_slot(&mut binding, &process_id, resource_id)?;
+    sim.0.wake();
 
-    match resources.iter().find(|r| r.id() == resource_id).cloned() {
-        Some(resource_found) => return Ok(sim.0.remove_resource(&resource_found)),
-        None => return Err("Resource not found".to_string()),
-    }
+    Ok(())
 }
 
+/// Drops the slot the process holds for the resource.
+pub fn remove_process_slot(
+    processes: &mut [ProcessStates],
+    process_id: &str,
+    resource_id: String,
+) -> Result<(), SimulationError> {
+    processes
+        .iter_mut()
+        .find(|p| p.id() == process_id)
+        .ok_or(SimulationError::ProcessNotFound)?
+        .process_mut()
+        .remove_resource(resource_id)
+        .ok_or(SimulationError::SlotNotFound)
+}
+
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn process_get_resource_intensity(
     app_handle: tauri::AppHandle,
     process_id: String,
-) -> Result<GenericProcessResourceIntensity, String> {
+) -> Result<GenericProcessResourceIntensity, SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
     let running_binding = sim.0.processes();
     let stopped_binding = sim.0.processes();
-    let processes = running_binding.lock().unwrap();
+    let processes = lock_state(&running_binding)?;
     match processes
         .iter()
         .find(|p| match p {
@@ -411,52 +443,51 @@ pub fn process_get_resource_intensity(
             ProcessStates::Blocked(process) => return Ok(process.resource_intensity),
             ProcessStates::Working(process) => return Ok(process.resource_intensity),
         },
-        None => return Err("Process not found".to_string()),
+        None => return Err(SimulationError::ProcessNotFound),
     }
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn process_set_name(
     app_handle: tauri::AppHandle,
     process_id: String,
     name: String,
-) -> Result<(), String> {
+) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
-    let running_binding = sim.0.processes();
-    let stopped_binding = sim.0.processes();
-    let processes = running_binding.lock().unwrap();
+    let sim = lock_state(&state)?;
+    let binding = sim.0.processes();
+    let mut processes = lock_state(&binding)?;
+    set_process_name(&mut processes, &process_id, name)
+}
 
-    match processes
-        .iter()
-        .find(|p| match p {
-            ProcessStates::Ready(process) => process.id == process_id,
-            ProcessStates::Blocked(process) => process.id == process_id,
-            ProcessStates::Working(process) => process.id == process_id,
-        })
-        .cloned()
-    {
-        Some(process_found) => match process_found {
-            ProcessStates::Ready(mut process) => Ok(process.set_name(name)),
-            ProcessStates::Blocked(mut process) => Ok(process.set_name(name)),
-            ProcessStates::Working(mut process) => Ok(process.set_name(name)),
-        },
-        None => {
-            return Err("Process not found".to_string());
-        }
-    }
+/// Renames the process in place, whatever state it is in.
+pub fn set_process_name(
+    processes: &mut [ProcessStates],
+    process_id: &str,
+    name: String,
+) -> Result<(), SimulationError> {
+    validate_name(&name)?;
+    processes
+        .iter_mut()
+        .find(|p| p.id() == process_id)
+        .ok_or(SimulationError::ProcessNotFound)?
+        .process_mut()
+        .set_name(name);
+    Ok(())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn process_get_name(
     app_handle: tauri::AppHandle,
     process_id: String,
-) -> Result<String, String> {
+) -> Result<String, SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
     let running_binding = sim.0.processes();
     let stopped_binding = sim.0.processes();
-    let processes = running_binding.lock().unwrap();
+    let processes = lock_state(&running_binding)?;
 
     match processes
         .iter()
@@ -472,38 +503,102 @@ pub fn process_get_name(
             ProcessStates::Blocked(process) => return Ok(process.name),
             ProcessStates::Working(process) => return Ok(process.name),
         },
-        None => return Err("Process not found".to_string()),
+        None => return Err(SimulationError::ProcessNotFound),
     }
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn process_set_resource_intensity(
     app_handle: tauri::AppHandle,
     process_id: String,
     resource_intensity: GenericProcessResourceIntensity,
-) {
+) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
-    let running_binding = sim.0.processes();
-    let stopped_binding = sim.0.processes();
-    let processes = running_binding.lock().unwrap();
+    let sim = lock_state(&state)?;
+    let binding = sim.0.processes();
+    let mut processes = lock_state(&binding)?;
+    set_process_resource_intensity(&mut processes, &process_id, resource_intensity)
+}
 
-    match processes
-        .iter()
-        .find(|p| match p {
-            ProcessStates::Ready(process) => process.id == process_id,
-            ProcessStates::Blocked(process) => process.id == process_id,
-            ProcessStates::Working(process) => process.id == process_id,
-        })
-        .cloned()
-    {
-        Some(process_found) => match process_found {
-            ProcessStates::Ready(mut process) => process.resource_intensity = resource_intensity,
-            ProcessStates::Blocked(mut process) => process.resource_intensity = resource_intensity,
-            ProcessStates::Working(mut process) => process.resource_intensity = resource_intensity,
-        },
-        None => {
-            return;
-        }
+/// Changes how often the process asks for resources, in place.
+pub fn set_process_resource_intensity(
+    processes: &mut [ProcessStates],
+    process_id: &str,
+    resource_intensity: GenericProcessResourceIntensity,
+) -> Result<(), SimulationError> {
+    processes
+        .iter_mut()
+        .find(|p| p.id() == process_id)
+        .ok_or(SimulationError::ProcessNotFound)?
+        .process_mut()
+        .set_resource_intensity(resource_intensity);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::ValidationError;
+
+    fn processes() -> (Vec<ProcessStates>, String) {
+        let process = Process::new("Editor".to_string(), GenericProcessResourceIntensity::Low);
+        let id = process.id();
+        (vec![ProcessStates::Ready(process)], id)
+    }
+
+    #[test]
+    fn rename_is_kept_and_a_blank_name_changes_nothing() {
+        let (mut processes, id) = processes();
+
+        set_process_name(&mut processes, &id, "Compiler".to_string()).unwrap();
+        assert_eq!(processes[0].process().name(), "Compiler");
+
+        assert!(matches!(
+            set_process_name(&mut processes, &id, "  ".to_string()),
+            Err(SimulationError::Invalid(ValidationError::EmptyName))
+        ));
+        assert_eq!(processes[0].process().name(), "Compiler");
+
+        assert!(matches!(
+            set_process_name(&mut processes, "missing", "Shell".to_string()),
+            Err(SimulationError::ProcessNotFound)
+        ));
+    }
+
+    #[test]
+    fn resource_intensity_is_kept() {
+        let (mut processes, id) = processes();
+
+        set_process_resource_intensity(
+            &mut processes,
+            &id,
+            GenericProcessResourceIntensity::Extreme,
+        )
+        .unwrap();
+        assert_eq!(
+            *processes[0].process().resource_intensity(),
+            GenericProcessResourceIntensity::Extreme
+        );
+    }
+
+    #[test]
+    fn removing_a_missing_slot_is_reported() {
+        let resource = GenericResource::new("Memory".to_string(), 100, false);
+        let (mut processes, id) = processes();
+        processes[0].process_mut().add_resource(&resource, 10);
+
+        assert!(matches!(
+            remove_process_slot(&mut processes, &id, "missing".to_string()),
+            Err(SimulationError::SlotNotFound)
+        ));
+        assert_eq!(processes[0].process().resource_slot().len(), 1);
+
+        remove_process_slot(&mut processes, &id, resource.id()).unwrap();
+        assert!(processes[0].process().resource_slot().is_empty());
+        assert!(matches!(
+            remove_process_slot(&mut processes, &id, resource.id()),
+            Err(SimulationError::SlotNotFound)
+        ));
     }
 }
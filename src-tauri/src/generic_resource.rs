@@ -2,6 +2,8 @@
 
 use nanoid::nanoid;
 
+use crate::validation::{validate_name, ValidationError};
+
 #[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
 pub struct GenericResource {
     name: String,
@@ -34,12 +36,19 @@ impl GenericResource {
         self.name = name;
     }
 
+    pub fn blocking(&self) -> bool {
+        self.blocking
+    }
+
     pub fn total_amount(&self) -> u64 {
         self.total_amount
     }
 
+    /// Keeps the amount in use constant, so the free amount moves with the total.
     pub fn set_total_amount(&mut self, total_amount: u64) {
+        let in_use = self.total_amount.saturating_sub(self.free_amount);
         self.total_amount = total_amount;
+        self.free_amount = total_amount.saturating_sub(in_use);
     }
 
     pub fn free_amount(&self) -> u64 {
@@ -70,32 +79,48 @@ impl PartialEq for GenericResource {
     }
 }
 
-#[tauri::command]
-pub fn create_resource(name: String, total_amount: u64, blocking: bool) -> GenericResource {
-    GenericResource::new(name, total_amount, blocking)
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn create_resource(
+    name: String,
+    total_amount: u64,
+    blocking: bool,
+) -> Result<GenericResource, ValidationError> {
+    validate_name(&name)?;
+    Ok(GenericResource::new(name, total_amount, blocking))
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_resource_name(resource: GenericResource) -> String {
     resource.name().to_string()
 }
 
-#[tauri::command]
-pub fn set_resource_name(mut resource: GenericResource, name: String) {
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_resource_name(
+    mut resource: GenericResource,
+    name: String,
+) -> Result<(), ValidationError> {
+    validate_name(&name)?;
     resource.set_name(name);
+    Ok(())
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_resource_total_amount(resource: GenericResource) -> u64 {
     resource.total_amount()
 }
 
-#[tauri::command]
-pub fn set_resource_total_amount(mut resource: GenericResource, total_amount: u64) {
+/// Only changes the copy it is given. `simulation_set_resource_total_amount` changes
+/// the live resource and checks the new total against the processes holding it.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn set_resource_total_amount(
+    mut resource: GenericResource,
+    total_amount: u64,
+) -> Result<(), ValidationError> {
     resource.set_total_amount(total_amount);
+    Ok(())
 }
 
-#[tauri::command]
+#[cfg_attr(feature = "desktop", tauri::command)]
 pub fn get_resource_free_amount(resource: GenericResource) -> u64 {
     resource.free_amount()
 }
@@ -0,0 +1,129 @@
+#[cfg(feature = "server")]
+pub mod api;
+pub mod autosave;
+pub mod batch;
+pub mod cgroup;
+pub mod clock;
+#[cfg(all(unix, feature = "desktop"))]
+mod control;
+pub mod discrete;
+pub mod environment;
+pub mod events;
+pub mod generic_process;
+pub mod generic_resource;
+pub mod locks;
+pub mod monitor;
+pub mod procfs;
+pub mod save_state;
+pub mod scenario;
+mod scheduler;
+mod schema;
+pub mod simulation;
+pub mod snapshot;
+pub mod validation;
+
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+
+use crate::generic_process::*;
+use crate::generic_resource::*;
+use crate::simulation::*;
+
+#[cfg(feature = "desktop")]
+use window_vibrancy::*;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+#[cfg(feature = "desktop")]
+pub struct TauriSim(RunningSimulation);
+
+#[cfg(feature = "desktop")]
+pub fn run() {
+    tauri::Builder::default()
+        .invoke_handler(tauri::generate_handler![
+            generic_resource::create_resource,
+            generic_resource::get_resource_name,
+            generic_resource::set_resource_name,
+            generic_resource::get_resource_total_amount,
+            generic_resource::set_resource_total_amount,
+            generic_resource::get_resource_free_amount,
+            generic_process::create_process,
+            generic_process::process_add_resource,
+            generic_process::process_remove_resource,
+            generic_process::process_get_resource_intensity,
+            generic_process::process_set_name,
+            generic_process::process_get_name,
+            generic_process::process_set_resource_intensity,
+            simulation::simulation_remove_process,
+            simulation::simulation_add_process,
+            simulation::simulation_add_resource,
+            simulation::simulation_remove_resource,
+            simulation::simulation_processes,
+            simulation::simulation_resources,
+            simulation::simulation_set_resource_total_amount,
+            simulation::simulation_set_simulation_speed,
+            simulation::simulation_speed,
+            simulation::simulation_set_unbounded,
+            simulation::simulation_set_publish_rate,
+            simulation::simulation_tick_rate,
+            simulation::simulation_time,
+            simulation::simulation_set_time_step,
+            simulation::simulation_engine,
+            simulation::simulation_set_engine,
+            simulation::simulation_set_seed,
+            simulation::stop_simulation,
+            simulation::start_simulation,
+            simulation::simulation_resync,
+            simulation::simulation_health,
+            batch::simulation_apply_batch,
+            scenario::simulation_load_scenario,
+            scenario::simulation_save_scenario,
+            procfs::simulation_import_system,
+            monitor::system_monitor_start,
+            monitor::system_monitor_stop,
+            locks::system_lock_report,
+            cgroup::system_cgroup_report,
+            cgroup::simulation_import_cgroup,
+            save_state::simulation_save_state,
+            save_state::simulation_restore_state,
+            autosave::autosave_settings,
+            autosave::autosave_set_settings,
+            autosave::autosave_list,
+            autosave::autosave_restore,
+            simulation::simulation_recover,
+            api::api_server_start,
+            api::api_server_stop,
+            api::api_server_status,
+        ])
+        .setup(move |app| {
+            app.manage(Mutex::new(TauriSim(RunningSimulation::new())));
+            app.manage(Mutex::new(api::ApiServerState::default()));
+            app.manage(Mutex::new(monitor::SystemMonitorState::default()));
+
+            let window = app.get_webview_window("main").unwrap();
+
+            #[cfg(target_os = "macos")]
+            apply_vibrancy(&window, NSVisualEffectMaterial::HudWindow, None, None)
+                .expect("Unsupported platform! 'apply_vibrancy' is only supported on macOS");
+
+            #[cfg(target_os = "windows")]
+            // apply_acrylic(&window, Some((0, 0, 0, 0)))
+            apply_acrylic(&window, Some((18, 18, 18, 125)))
+                .expect("Unsupported platform! 'apply_blur' is only supported on Windows");
+
+            // Add decorators to the window
+            window
+                .set_decorations(true)
+                .expect("Failed to set window decorations");
+
+            simulation::start_simulation(app.app_handle().clone())?;
+            autosave::start_autosave(app.app_handle().clone())?;
+            #[cfg(unix)]
+            control::start_control_socket(app.app_handle().clone())?;
+
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,225 @@
+//! File locks held on the host and the processes deadlocked on them.
+//!
+//! The simulation has no graph or cycle detection to reuse: `safe_to_continue`
+//! checks amounts against totals and `discrete::deadlocked` only asks whether every
+//! process is blocked. A lock has a single owner, so here a deadlock is a cycle in
+//! `WaitForGraph`, which is its own implementation.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::procfs::{self, SystemError, PROC_ROOT};
+use crate::simulation::SimulationError;
+
+/// A line of `/proc/locks`.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct FileLock {
+    /// Number of the lock, shared by a held lock and the requests blocked on it.
+    pub id: u64,
+    /// `POSIX`, `FLOCK`, `OFDLCK`, `LEASE` or `DELEG`.
+    pub kind: String,
+    /// `READ`, `WRITE` or `UNLCK`.
+    pub access: String,
+    /// `None` for open file description locks, which belong to no process.
+    pub pid: Option<u32>,
+    /// `major:minor:inode` of the locked file.
+    pub file: String,
+    /// Pid of the lock this request waits on, `None` when the lock is held.
+    pub blocked_by: Option<u32>,
+}
+
+impl FileLock {
+    /// Parses the whole file. Blocked requests follow the lock they wait on, indented
+    /// one level deeper and marked `->`.
+    pub fn parse(contents: &str) -> Result<Vec<Self>, SystemError> {
+        let mut locks = vec![];
+        // Pid of the last lock seen at each depth, `parents[0]` being a held lock.
+        let mut parents: Vec<Option<u32>> = vec![];
+
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let invalid = || SystemError::Parse(format!("invalid lock '{}'", line.trim()));
+
+            let (id, rest) = line.split_once(':').ok_or_else(invalid)?;
+            let id = id.trim().parse().map_err(|_| invalid())?;
+
+            // The kernel indents every level of waiting by one more space.
+            let (depth, rest) = match rest.find("->") {
+                Some(arrow) => (arrow, &rest[arrow + 2..]),
+                None => (0, rest),
+            };
+            let fields: Vec<&str> = rest.split_whitespace().collect();
+            let [kind, _mode, access, pid, file, ..] = fields[..] else {
+                return Err(invalid());
+            };
+            let pid = match pid.parse::<i64>().map_err(|_| invalid())? {
+                pid if pid > 0 => Some(pid as u32),
+                _ => None,
+            };
+
+            let blocked_by = match depth {
+                0 => None,
+                depth => parents.get(depth - 1).copied().flatten(),
+            };
+            parents.truncate(depth);
+            parents.push(pid);
+
+            locks.push(FileLock {
+                id,
+                kind: kind.to_string(),
+                access: access.to_string(),
+                pid,
+                file: file.to_string(),
+                blocked_by,
+            });
+        }
+
+        Ok(locks)
+    }
+}
+
+/// Edges from each process to the processes it waits on.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct WaitForGraph {
+    edges: BTreeMap<u32, BTreeSet<u32>>,
+}
+
+impl WaitForGraph {
+    pub fn from_locks(locks: &[FileLock]) -> Self {
+        let mut graph = WaitForGraph::default();
+        for lock in locks {
+            if let (Some(waiter), Some(holder)) = (lock.pid, lock.blocked_by) {
+                graph.add(waiter, holder);
+            }
+        }
+        graph
+    }
+
+    pub fn add(&mut self, waiter: u32, holder: u32) {
+        self.edges.entry(waiter).or_default().insert(holder);
+    }
+
+    /// Every cycle, each listed once and starting at its smallest pid. A process
+    /// waiting on itself is a cycle of one.
+    pub fn cycles(&self) -> Vec<Vec<u32>> {
+        let mut cycles = vec![];
+        for start in self.edges.keys() {
+            self.search(*start, *start, &mut vec![*start], &mut cycles);
+        }
+        cycles
+    }
+
+    /// Walks every path from `start` through larger pids only, so each cycle is found
+    /// from its smallest pid and nowhere else.
+    fn search(&self, start: u32, node: u32, path: &mut Vec<u32>, cycles: &mut Vec<Vec<u32>>) {
+        for next in self.edges.get(&node).into_iter().flatten() {
+            if *next == start {
+                cycles.push(path.clone());
+            } else if *next > start && !path.contains(next) {
+                path.push(*next);
+                self.search(start, *next, path, cycles);
+                path.pop();
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct LockedProcess {
+    pub pid: u32,
+    /// Empty when the process exited while being read.
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct LockReport {
+    pub locks: Vec<FileLock>,
+    /// Processes that wait on each other forever, in the order they wait.
+    pub cycles: Vec<Vec<LockedProcess>>,
+}
+
+impl LockReport {
+    pub fn read(root: &Path) -> Result<Self, SimulationError> {
+        if !cfg!(target_os = "linux") {
+            return Err(SystemError::Unsupported.into());
+        }
+
+        let locks = FileLock::parse(&procfs::read(&root.join("locks"))?)?;
+        let cycles = WaitForGraph::from_locks(&locks)
+            .cycles()
+            .into_iter()
+            .map(|cycle| {
+                cycle
+                    .into_iter()
+                    .map(|pid| LockedProcess {
+                        pid,
+                        name: procfs::read(&root.join(pid.to_string()).join("comm"))
+                            .map(|name| name.trim().to_string())
+                            .unwrap_or_default(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Ok(LockReport { locks, cycles })
+    }
+}
+
+/// File locks held and waited on right now, and the processes deadlocked on them.
+#[cfg_attr(feature = "desktop", tauri::command)]
+pub fn system_lock_report() -> Result<LockReport, SimulationError> {
+    LockReport::read(Path::new(PROC_ROOT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCKS: &str = "\
+1: POSIX  ADVISORY  WRITE 100 08:01:1234 0 EOF
+1: -> POSIX  ADVISORY  WRITE 200 08:01:1234 0 EOF
+1:  -> POSIX  ADVISORY  WRITE 300 08:01:1234 0 EOF
+2: FLOCK  ADVISORY  READ  200 08:01:5678 0 EOF
+2: -> FLOCK  ADVISORY  WRITE 100 08:01:5678 0 EOF
+3: OFDLCK ADVISORY  READ  -1 00:05:42 0 EOF
+";
+
+    #[test]
+    fn blocked_requests_wait_on_the_lock_one_level_up() {
+        let locks = FileLock::parse(LOCKS).unwrap();
+        let waits: Vec<(u64, Option<u32>, Option<u32>)> = locks
+            .iter()
+            .map(|lock| (lock.id, lock.pid, lock.blocked_by))
+            .collect();
+        assert_eq!(
+            waits,
+            vec![
+                (1, Some(100), None),
+                (1, Some(200), Some(100)),
+                (1, Some(300), Some(200)),
+                (2, Some(200), None),
+                (2, Some(100), Some(200)),
+                (3, None, None),
+            ]
+        );
+        assert_eq!(locks[3].kind, "FLOCK");
+        assert_eq!(locks[3].access, "READ");
+        assert_eq!(locks[5].file, "00:05:42");
+
+        assert!(FileLock::parse("1: POSIX ADVISORY WRITE").is_err());
+    }
+
+    #[test]
+    fn processes_waiting_on_each_other_form_cycles() {
+        let graph = WaitForGraph::from_locks(&FileLock::parse(LOCKS).unwrap());
+        assert_eq!(graph.cycles(), vec![vec![100, 200]]);
+    }
+
+    #[test]
+    fn every_cycle_is_listed_once() {
+        let mut graph = WaitForGraph::default();
+        for (waiter, holder) in [(1, 2), (1, 3), (2, 3), (3, 1), (5, 5), (6, 7)] {
+            graph.add(waiter, holder);
+        }
+        assert_eq!(graph.cycles(), vec![vec![1, 2, 3], vec![1, 3], vec![5]]);
+    }
+}
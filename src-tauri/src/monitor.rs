@@ -0,0 +1,376 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+use crate::events::{EventSink, SimulationEvent};
+use crate::generic_resource::GenericResource;
+use crate::procfs::{self, SystemError, SystemInfo};
+use crate::simulation::{
+    lock_state, AllSimulationTrait, ResourceRemovalPolicy, RunningSimulation, SimulationError,
+};
+#[cfg(feature = "desktop")]
+use crate::{procfs::PROC_ROOT, TauriSim};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Busy and total jiffies of all CPUs, from the first line of `/proc/stat`.
+#[derive(Clone, Copy, Debug)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    fn read(root: &Path) -> Result<Self, SystemError> {
+        let stat = procfs::read(&root.join("stat"))?;
+        let times: Vec<u64> = stat
+            .lines()
+            .find(|line| line.starts_with("cpu "))
+            .ok_or(SystemError::Parse("missing cpu line in stat".to_string()))?
+            .split_whitespace()
+            .skip(1)
+            .filter_map(|value| value.parse().ok())
+            .collect();
+
+        // idle and iowait are the 4th and 5th columns.
+        let idle = times.iter().skip(3).take(2).sum::<u64>();
+        let total = times.iter().sum::<u64>();
+        Ok(CpuTimes {
+            busy: total - idle,
+            total,
+        })
+    }
+
+    fn usage_since(&self, previous: &CpuTimes) -> f64 {
+        let total = self.total.saturating_sub(previous.total);
+        if total == 0 {
+            return 0.0;
+        }
+        self.busy.saturating_sub(previous.busy) as f64 / total as f64 * 100.0
+    }
+}
+
+/// One reading of the machine.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct SystemSample {
+    /// Busy time of all CPUs together since the previous sample.
+    pub cpu_percent: f64,
+    pub cores: u64,
+    pub memory_total_kib: u64,
+    pub memory_available_kib: u64,
+    /// Over 1, 5 and 15 minutes.
+    pub load_average: [f64; 3],
+}
+
+impl SystemSample {
+    fn read(root: &Path, cores: u64, cpu: &mut CpuTimes) -> Result<Self, SystemError> {
+        let times = CpuTimes::read(root)?;
+        let cpu_percent = times.usage_since(cpu);
+        *cpu = times;
+
+        let meminfo = procfs::read(&root.join("meminfo"))?;
+        let loadavg = procfs::read(&root.join("loadavg"))?;
+        let mut load_average = [0.0; 3];
+        for (average, value) in load_average.iter_mut().zip(loadavg.split_whitespace()) {
+            *average = value
+                .parse()
+                .map_err(|_| SystemError::Parse("invalid loadavg".to_string()))?;
+        }
+
+        Ok(SystemSample {
+            cpu_percent,
+            cores,
+            memory_total_kib: procfs::number(&meminfo, "MemTotal")?,
+            memory_available_kib: procfs::number(&meminfo, "MemAvailable")?,
+            load_average,
+        })
+    }
+}
+
+/// Ids of the resources the monitor keeps up to date, and what the machine used of
+/// each at the last sample.
+#[derive(Clone)]
+struct Monitored {
+    cpu: String,
+    memory: String,
+    load: String,
+    used: [u64; 3],
+}
+
+impl Monitored {
+    /// CPU and load are in percent of the whole machine, memory in MiB.
+    ///
+    /// Processes of the discrete engine may hold amounts of these resources too. What
+    /// is in use beyond the machine's own usage of the last sample is theirs, so it is
+    /// taken out of the new free amount instead of being overwritten.
+    fn update(&mut self, resources: &mut [GenericResource], sample: &SystemSample) {
+        let load = sample.load_average[0] / sample.cores.max(1) as f64 * 100.0;
+        let memory_total = sample.memory_total_kib / 1024;
+        let memory_free = sample.memory_available_kib / 1024;
+
+        for resource in resources.iter_mut() {
+            let (index, total, used) = if resource.id() == self.cpu {
+                (0, 100, sample.cpu_percent.round() as u64)
+            } else if resource.id() == self.memory {
+                (1, memory_total, memory_total.saturating_sub(memory_free))
+            } else if resource.id() == self.load {
+                (2, 100, load.round() as u64)
+            } else {
+                continue;
+            };
+
+            let in_use = resource
+                .total_amount()
+                .saturating_sub(resource.free_amount());
+            let held = in_use.saturating_sub(self.used[index]);
+            self.used[index] = used;
+
+            resource.set_total_amount(total);
+            resource.set_free_amount(total.saturating_sub(used).saturating_sub(held));
+        }
+    }
+
+    fn ids(&self) -> [&String; 3] {
+        [&self.cpu, &self.memory, &self.load]
+    }
+}
+
+/// Samples `/proc` on an interval into dedicated resources of a simulation, and emits
+/// every sample on the simulation's event channel. Stops when dropped.
+pub struct SystemMonitor {
+    stop: Sender<()>,
+    simulation: RunningSimulation,
+    monitored: Monitored,
+}
+
+impl SystemMonitor {
+    pub fn start<S: EventSink>(
+        mut simulation: RunningSimulation,
+        sink: S,
+        root: PathBuf,
+        interval: Duration,
+    ) -> Result<Self, SimulationError> {
+        if !cfg!(target_os = "linux") {
+            return Err(SystemError::Unsupported.into());
+        }
+
+        let cores = SystemInfo::read(&root)?.cores;
+        let mut cpu = CpuTimes::read(&root)?;
+        let first = SystemSample::read(&root, cores, &mut cpu)?;
+
+        let mut resources = [
+            GenericResource::new("CPU (%)".to_string(), 100, false),
+            GenericResource::new("Memory (MiB)".to_string(), 0, false),
+            GenericResource::new("Load (%)".to_string(), 100, false),
+        ];
+        let mut monitored = Monitored {
+            cpu: resources[0].id(),
+            memory: resources[1].id(),
+            load: resources[2].id(),
+            used: [0; 3],
+        };
+        monitored.update(&mut resources, &first);
+        for resource in resources {
+            simulation.add_resource(resource)?;
+        }
+        simulation.wake();
+
+        let (stop, stopped) = channel();
+        let thread_simulation = simulation.clone();
+        let mut ids = monitored.clone();
+        thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+
+            // Reporting the same error every interval helps nobody, so give up on the
+            // first one. The resources stay until `stop` removes them.
+            let sample = match SystemSample::read(&root, cores, &mut cpu) {
+                Ok(sample) => sample,
+                Err(e) => {
+                    thread_simulation.events().emit(
+                        &sink,
+                        SimulationEvent::MonitorFailed {
+                            reason: e.to_string(),
+                        },
+                    );
+                    return;
+                }
+            };
+
+            let resources = thread_simulation.resources();
+            match lock_state(&resources) {
+                Ok(mut resources) => ids.update(&mut resources, &sample),
+                Err(_) => return,
+            }
+            thread_simulation.wake();
+            thread_simulation
+                .events()
+                .emit(&sink, SimulationEvent::SystemSample(sample));
+        });
+
+        Ok(SystemMonitor {
+            stop,
+            simulation,
+            monitored,
+        })
+    }
+
+    /// Stops sampling and removes the monitored resources.
+    pub fn stop(mut self) -> Result<(), SimulationError> {
+        let _ = self.stop.send(());
+        for id in self.monitored.ids() {
+            match self
+                .simulation
+                .remove_resource_by_id(id.clone(), ResourceRemovalPolicy::ReleaseSlots)
+            {
+                Ok(_) | Err(SimulationError::ResourceNotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.simulation.wake();
+        Ok(())
+    }
+}
+
+/// The monitor started from the window, if any.
+#[cfg(feature = "desktop")]
+#[derive(Default)]
+pub struct SystemMonitorState(Option<SystemMonitor>);
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn system_monitor_start(
+    app_handle: tauri::AppHandle,
+    interval_ms: Option<u64>,
+) -> Result<(), SimulationError> {
+    let monitors = app_handle.state::<Mutex<SystemMonitorState>>();
+    let mut monitors = lock_state(&monitors)?;
+    if monitors.0.is_some() {
+        return Err(SystemError::AlreadyMonitoring.into());
+    }
+
+    let simulation = {
+        let state = app_handle.state::<Mutex<TauriSim>>();
+        let sim = lock_state(&state)?;
+        sim.0.clone()
+    };
+
+    let interval = interval_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_INTERVAL)
+        .max(Duration::from_millis(100));
+    monitors.0 = Some(SystemMonitor::start(
+        simulation,
+        app_handle.clone(),
+        PathBuf::from(PROC_ROOT),
+        interval,
+    )?);
+    Ok(())
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn system_monitor_stop(app_handle: tauri::AppHandle) -> Result<(), SimulationError> {
+    let monitors = app_handle.state::<Mutex<SystemMonitorState>>();
+    let mut monitors = lock_state(&monitors)?;
+    let monitor = monitors.0.take().ok_or(SystemError::NotMonitoring)?;
+    monitor.stop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Recorder;
+    use crate::simulation::tests::{temp_dir, within_timeout};
+    use std::fs;
+
+    fn sample(cpu_percent: f64, memory_available_kib: u64) -> SystemSample {
+        SystemSample {
+            cpu_percent,
+            cores: 2,
+            memory_total_kib: 1024 * 1024,
+            memory_available_kib,
+            load_average: [1.0, 0.5, 0.25],
+        }
+    }
+
+    #[test]
+    fn updates_keep_what_the_simulation_holds() {
+        let mut resources = [
+            GenericResource::new("CPU (%)".to_string(), 100, false),
+            GenericResource::new("Memory (MiB)".to_string(), 0, false),
+            GenericResource::new("Load (%)".to_string(), 100, false),
+        ];
+        let mut monitored = Monitored {
+            cpu: resources[0].id(),
+            memory: resources[1].id(),
+            load: resources[2].id(),
+            used: [0; 3],
+        };
+
+        monitored.update(&mut resources, &sample(30.0, 768 * 1024));
+        let free: Vec<u64> = resources.iter().map(|r| r.free_amount()).collect();
+        assert_eq!(free, vec![70, 768, 50]);
+
+        // A process of the simulation takes 10% of the CPU.
+        resources[0].set_free_amount(60);
+        monitored.update(&mut resources, &sample(40.0, 768 * 1024));
+        assert_eq!(resources[0].free_amount(), 50);
+        assert_eq!(resources[1].free_amount(), 768);
+
+        // And gives it back.
+        resources[0].set_free_amount(60);
+        monitored.update(&mut resources, &sample(40.0, 768 * 1024));
+        assert_eq!(resources[0].free_amount(), 60);
+    }
+
+    #[test]
+    fn sampling_stops_with_an_event_when_proc_cannot_be_read() {
+        let root = temp_dir("monitor");
+        fs::create_dir_all(root.join("sys/fs")).unwrap();
+        fs::write(root.join("stat"), "cpu  10 0 10 80 0 0 0 0 0 0\n").unwrap();
+        fs::write(
+            root.join("meminfo"),
+            "MemTotal:       1048576 kB\nMemAvailable:    524288 kB\n",
+        )
+        .unwrap();
+        fs::write(root.join("loadavg"), "0.50 0.25 0.10 1/100 1234\n").unwrap();
+        fs::write(root.join("cpuinfo"), "processor\t: 0\n").unwrap();
+        fs::write(root.join("sys/fs/file-max"), "1000\n").unwrap();
+
+        let simulation = RunningSimulation::new();
+        let sink = Recorder::default();
+        let monitor = SystemMonitor::start(
+            simulation.clone(),
+            sink.clone(),
+            root.clone(),
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        assert_eq!(simulation.resources().lock().unwrap().len(), 3);
+
+        fs::remove_file(root.join("loadavg")).unwrap();
+        within_timeout(move || loop {
+            let events = sink.events();
+            if events
+                .iter()
+                .any(|e| matches!(e, SimulationEvent::MonitorFailed { .. }))
+            {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        });
+
+        monitor.stop().unwrap();
+        assert!(simulation.resources().lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+use crate::generic_process::{
+    AllProcessTraits, GenericProcessResourceIntensity, Process, ProcessStates,
+};
+use crate::generic_resource::GenericResource;
+#[cfg(feature = "desktop")]
+use crate::simulation::lock_state;
+use crate::simulation::SimulationError;
+#[cfg(feature = "desktop")]
+use crate::{snapshot::Snapshot, TauriSim};
+
+pub const PROC_ROOT: &str = "/proc";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SystemError {
+    Unsupported,
+    Io(String),
+    Parse(String),
+    AlreadyMonitoring,
+    NotMonitoring,
+    NotCgroup(String),
+}
+
+impl fmt::Display for SystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SystemError::Unsupported => write!(f, "Reading the system is only supported on Linux"),
+            SystemError::Io(error) => write!(f, "Could not read the system: {}", error),
+            SystemError::Parse(error) => write!(f, "Could not parse the system: {}", error),
+            SystemError::AlreadyMonitoring => write!(f, "The system is already being monitored"),
+            SystemError::NotMonitoring => write!(f, "The system is not being monitored"),
+            SystemError::NotCgroup(path) => write!(f, "{} is not a cgroup v2 directory", path),
+        }
+    }
+}
+
+impl std::error::Error for SystemError {}
+
+impl serde::Serialize for SystemError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub(crate) fn read(path: &Path) -> Result<String, SystemError> {
+    fs::read_to_string(path).map_err(|e| SystemError::Io(format!("{}: {}", path.display(), e)))
+}
+
+/// Value of a `Key: value` line, as in `/proc/meminfo` or `/proc/<pid>/status`.
+pub(crate) fn field<'a>(contents: &'a str, key: &str) -> Option<&'a str> {
+    contents.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
+/// First number of a field, ignoring units like `kB`.
+pub(crate) fn number(contents: &str, key: &str) -> Result<u64, SystemError> {
+    field(contents, key)
+        .and_then(|value| value.split_whitespace().next())
+        .and_then(|value| value.parse().ok())
+        .ok_or(SystemError::Parse(format!("missing or invalid '{}'", key)))
+}
+
+/// A process as read from `/proc/<pid>`.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub name: String,
+    /// Single letter state from `status`, like `R`, `S` or `D`.
+    pub state: char,
+    pub rss_kib: u64,
+    pub threads: u64,
+    /// `None` when the process belongs to someone else and its fds cannot be listed.
+    pub fds: Option<u64>,
+}
+
+/// Sizes of the whole machine, used as resource totals.
+#[derive(Clone, PartialEq, serde::Serialize, Debug)]
+pub struct SystemInfo {
+    pub mem_total_kib: u64,
+    pub cores: u64,
+    pub file_max: u64,
+}
+
+impl SystemInfo {
+    pub fn read(root: &Path) -> Result<Self, SystemError> {
+        let meminfo = read(&root.join("meminfo"))?;
+        let cpuinfo = read(&root.join("cpuinfo"))?;
+        let file_max = read(&root.join("sys/fs/file-max"))?;
+
+        Ok(SystemInfo {
+            mem_total_kib: number(&meminfo, "MemTotal")?,
+            cores: cpuinfo
+                .lines()
+                .filter(|line| line.split(':').next().map(str::trim) == Some("processor"))
+                .count()
+                .max(1) as u64,
+            file_max: file_max
+                .trim()
+                .parse()
+                .map_err(|_| SystemError::Parse("invalid file-max".to_string()))?,
+        })
+    }
+}
+
+impl ProcInfo {
+    /// Kernel threads have no memory of their own and come back as `None`, as do
+    /// processes that exit while being read.
+    pub fn read(root: &Path, pid: u32) -> Option<Self> {
+        let dir = root.join(pid.to_string());
+        let status = read(&dir.join("status")).ok()?;
+        let rss_kib = number(&status, "VmRSS").ok()?;
+
+        let fds = fs::read_dir(dir.join("fd"))
+            .ok()
+            .map(|entries| entries.count() as u64);
+
+        Some(ProcInfo {
+            pid,
+            name: field(&status, "Name")?.to_string(),
+            state: field(&status, "State")?.chars().next()?,
+            rss_kib,
+            threads: number(&status, "Threads").ok()?,
+            fds,
+        })
+    }
+
+    /// Every user process, biggest resident memory first.
+    pub fn read_all(root: &Path) -> Result<Vec<Self>, SystemError> {
+        let entries = fs::read_dir(root)
+            .map_err(|e| SystemError::Io(format!("{}: {}", root.display(), e)))?;
+
+        let mut processes: Vec<ProcInfo> = entries
+            .flatten()
+            .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+            .filter_map(|pid| Self::read(root, pid))
+            .collect();
+        processes.sort_by(|a, b| b.rss_kib.cmp(&a.rss_kib).then(a.pid.cmp(&b.pid)));
+        Ok(processes)
+    }
+}
+
+/// Builds resources for memory (MiB), CPU cores and file descriptors, and one process
+/// per entry of `processes` holding its resident memory, one core per thread and its
+/// open fds, each capped at the total. Running and sleeping processes come in as
+/// ready, stopped ones and those in uninterruptible sleep as blocked, and zombies are
+/// left out. Kernel threads never get this far, `ProcInfo::read` already drops them.
+pub fn build(
+    system: &SystemInfo,
+    processes: &[ProcInfo],
+) -> (Vec<GenericResource>, Vec<ProcessStates>) {
+    let memory = GenericResource::new(
+        "Memory (MiB)".to_string(),
+        system.mem_total_kib / 1024,
+        false,
+    );
+    let cpu = GenericResource::new("CPU cores".to_string(), system.cores, false);
+    let fds = GenericResource::new("File descriptors".to_string(), system.file_max, false);
+
+    let mut imported = vec![];
+    for info in processes {
+        let intensity = match info.state {
+            'R' => GenericProcessResourceIntensity::High,
+            _ => GenericProcessResourceIntensity::Low,
+        };
+
+        let mut process = Process::new(format!("{} ({})", info.name, info.pid), intensity);
+        process.add_resource(&memory, (info.rss_kib / 1024).min(memory.total_amount()));
+        process.add_resource(&cpu, info.threads.min(cpu.total_amount()));
+        process.add_resource(&fds, info.fds.unwrap_or(0).min(fds.total_amount()));
+
+        match info.state {
+            'R' | 'S' => imported.push(ProcessStates::Ready(process)),
+            'D' | 'T' | 't' | 'W' => imported.push(ProcessStates::Blocked(process.block())),
+            _ => continue,
+        }
+    }
+
+    (vec![memory, cpu, fds], imported)
+}
+
+/// Reads the machine and up to `limit` of its processes, biggest first.
+pub fn import(
+    root: &Path,
+    limit: Option<usize>,
+) -> Result<(Vec<GenericResource>, Vec<ProcessStates>), SimulationError> {
+    if !cfg!(target_os = "linux") {
+        return Err(SystemError::Unsupported.into());
+    }
+
+    let system = SystemInfo::read(root)?;
+    let mut processes = ProcInfo::read_all(root)?;
+    processes.truncate(limit.unwrap_or(usize::MAX));
+
+    Ok(build(&system, &processes))
+}
+
+/// Replaces the simulation with the processes running on this machine.
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_import_system(
+    app_handle: tauri::AppHandle,
+    limit: Option<usize>,
+) -> Result<Snapshot, SimulationError> {
+    let (resources, processes) = import(Path::new(PROC_ROOT), limit)?;
+
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.replace(&app_handle, resources, processes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::tests::temp_dir;
+
+    fn info(pid: u32, state: char, rss_kib: u64) -> ProcInfo {
+        ProcInfo {
+            pid,
+            name: "worker".to_string(),
+            state,
+            rss_kib,
+            threads: 64,
+            fds: None,
+        }
+    }
+
+    #[test]
+    fn build_maps_states_and_caps_amounts() {
+        let system = SystemInfo {
+            mem_total_kib: 8 * 1024 * 1024,
+            cores: 4,
+            file_max: 1000,
+        };
+        let (resources, processes) = build(
+            &system,
+            &[
+                info(1, 'R', 2048),
+                info(2, 'S', 16 * 1024 * 1024),
+                info(3, 'D', 0),
+                info(4, 'Z', 0),
+            ],
+        );
+
+        let totals: Vec<u64> = resources.iter().map(|r| r.total_amount()).collect();
+        assert_eq!(totals, vec![8 * 1024, 4, 1000]);
+
+        assert_eq!(processes.len(), 3);
+        assert!(matches!(processes[0], ProcessStates::Ready(_)));
+        assert!(matches!(processes[1], ProcessStates::Ready(_)));
+        assert!(matches!(processes[2], ProcessStates::Blocked(_)));
+        assert_eq!(processes[0].process().name(), "worker (1)");
+        assert_eq!(
+            *processes[0].process().resource_intensity(),
+            GenericProcessResourceIntensity::High
+        );
+
+        let amounts = |process: &ProcessStates| -> Vec<u64> {
+            process
+                .process()
+                .resource_slot()
+                .iter()
+                .map(|slot| slot.base_amount())
+                .collect()
+        };
+        assert_eq!(amounts(&processes[0]), vec![2, 4, 0]);
+        assert_eq!(amounts(&processes[1]), vec![8 * 1024, 4, 0]);
+    }
+
+    #[test]
+    fn kernel_threads_are_not_read() {
+        let root = temp_dir("procfs");
+        for (pid, status) in [
+            (
+                1,
+                "Name:\tinit\nState:\tS (sleeping)\nVmRSS:\t  4096 kB\nThreads:\t1\n",
+            ),
+            (2, "Name:\tkthreadd\nState:\tI (idle)\nThreads:\t1\n"),
+        ] {
+            let dir = root.join(pid.to_string());
+            fs::create_dir_all(dir.join("fd")).unwrap();
+            fs::write(dir.join("status"), status).unwrap();
+        }
+        fs::write(root.join("1/fd/0"), "").unwrap();
+
+        let processes = ProcInfo::read_all(&root).unwrap();
+        assert_eq!(
+            processes,
+            vec![ProcInfo {
+                pid: 1,
+                name: "init".to_string(),
+                state: 'S',
+                rss_kib: 4096,
+                threads: 1,
+                fds: Some(1),
+            }]
+        );
+    }
+}
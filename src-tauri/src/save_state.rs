@@ -0,0 +1,172 @@
+use std::fmt;
+use std::path::Path;
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+use crate::clock::VirtualClock;
+use crate::discrete::{DiscreteEngine, Engine};
+use crate::generic_process::ProcessStates;
+use crate::generic_resource::GenericResource;
+use crate::schema::{Schema, SchemaError};
+use crate::simulation::SimulationRng;
+#[cfg(feature = "desktop")]
+use crate::{
+    simulation::{lock_state, SimulationError},
+    snapshot::Snapshot,
+    TauriSim,
+};
+
+pub const SAVE_STATE_SCHEMA: Schema = Schema {
+    name: "saved state",
+    version: 1,
+    migrations: &[],
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveStateError {
+    Io(String),
+    Parse(String),
+    Schema(SchemaError),
+    NoAutosave,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::Io(error) => write!(f, "Could not access saved state: {}", error),
+            SaveStateError::Parse(error) => write!(f, "Invalid saved state: {}", error),
+            SaveStateError::Schema(error) => write!(f, "{}", error),
+            SaveStateError::NoAutosave => write!(f, "No autosave to restore"),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl serde::Serialize for SaveStateError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Complete live state of a simulation. Unlike a scenario it keeps the current
+/// amounts, the clock, the rng and what the discrete engine has scheduled, so a
+/// restored simulation continues exactly where the saved one was.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SaveState {
+    pub version: u32,
+    pub clock: VirtualClock,
+    pub speed: f64,
+    pub last_speed: f64,
+    pub engine: Engine,
+    pub publish_rate: u64,
+    pub unbounded: bool,
+    pub rng: SimulationRng,
+    pub processes: Vec<ProcessStates>,
+    pub resources: Vec<GenericResource>,
+    pub discrete: DiscreteEngine,
+}
+
+impl SaveState {
+    pub fn read(path: &Path) -> Result<Self, SaveStateError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| SaveStateError::Io(e.to_string()))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, SaveStateError> {
+        let document: serde_json::Value =
+            serde_json::from_str(contents).map_err(|e| SaveStateError::Parse(e.to_string()))?;
+
+        let document = SAVE_STATE_SCHEMA
+            .upgrade(document)
+            .map_err(SaveStateError::Schema)?;
+        serde_json::from_value(document).map_err(|e| SaveStateError::Parse(e.to_string()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), SaveStateError> {
+        let contents = self.render()?;
+        std::fs::write(path, contents).map_err(|e| SaveStateError::Io(e.to_string()))
+    }
+
+    pub fn render(&self) -> Result<String, SaveStateError> {
+        serde_json::to_string(self).map_err(|e| SaveStateError::Parse(e.to_string()))
+    }
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_save_state(
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    let saved = sim.0.save_state()?;
+
+    Ok(saved.write(Path::new(&path))?)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_restore_state(
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<Snapshot, SimulationError> {
+    let saved = SaveState::read(Path::new(&path))?;
+
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.restore_state(&app_handle, saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::Recorder;
+    use crate::simulation::tests::simulation;
+    use crate::simulation::RunningSimulation;
+
+    #[test]
+    fn restored_simulations_continue_exactly_like_the_saved_one() {
+        for engine in [Engine::Ticked, Engine::DiscreteEvent] {
+            let sink = Recorder::default();
+            let original = simulation();
+            original.set_engine(engine).unwrap();
+            for _ in 0..20 {
+                original.step(&sink).unwrap();
+            }
+
+            let saved =
+                SaveState::parse(&original.save_state().unwrap().render().unwrap()).unwrap();
+            let restored = RunningSimulation::new();
+            restored.restore_state(&sink, saved).unwrap();
+
+            for _ in 0..50 {
+                original.step(&sink).unwrap();
+                restored.step(&sink).unwrap();
+                assert_eq!(
+                    restored.save_state().unwrap().render().unwrap(),
+                    original.save_state().unwrap().render().unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn newer_saves_are_refused() {
+        assert!(matches!(
+            SaveState::parse(r#"{ "version": 2 }"#),
+            Err(SaveStateError::Schema(SchemaError::TooNew {
+                version: 2,
+                ..
+            }))
+        ));
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+#[cfg(feature = "desktop")]
+use std::sync::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+
+use crate::discrete::Engine;
+use crate::generic_process::{
+    AllProcessTraits, GenericProcessResourceIntensity, Process, ProcessStates,
+};
+use crate::generic_resource::GenericResource;
+use crate::schema::{Schema, SchemaError};
+use crate::simulation::SimulationError;
+use crate::validation::{validate_name, validate_slot, validate_speed, validate_time_step};
+#[cfg(feature = "desktop")]
+use crate::{simulation::lock_state, snapshot::Snapshot, TauriSim};
+
+pub const SCENARIO_SCHEMA: Schema = Schema {
+    name: "scenario",
+    version: 1,
+    migrations: &[],
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScenarioError {
+    Io(String),
+    Parse(String),
+    UnsupportedFormat(String),
+    Schema(SchemaError),
+    DuplicateResource(String),
+    UnknownResource { process: String, resource: String },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(error) => write!(f, "Could not access scenario file: {}", error),
+            ScenarioError::Parse(error) => write!(f, "Invalid scenario: {}", error),
+            ScenarioError::UnsupportedFormat(extension) => write!(
+                f,
+                "Unsupported scenario format '{}', use .toml or .json",
+                extension
+            ),
+            ScenarioError::Schema(error) => write!(f, "{}", error),
+            ScenarioError::DuplicateResource(key) => {
+                write!(f, "Resource '{}' is defined more than once", key)
+            }
+            ScenarioError::UnknownResource { process, resource } => write!(
+                f,
+                "Process '{}' has a slot for unknown resource '{}'",
+                process, resource
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl serde::Serialize for ScenarioError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+#[serde(default)]
+pub struct ScenarioSettings {
+    pub speed: f64,
+    pub time_step: f64,
+    pub engine: Engine,
+    pub publish_rate: u64,
+    pub unbounded: bool,
+}
+
+impl Default for ScenarioSettings {
+    fn default() -> Self {
+        Self {
+            speed: 60.0,
+            time_step: 1.0,
+            engine: Engine::default(),
+            publish_rate: 30,
+            unbounded: false,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+pub struct ScenarioResource {
+    /// What slots use to refer to this resource. Defaults to the name, so it only
+    /// has to be written out when two resources share a name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    name: String,
+    total_amount: u64,
+    #[serde(default)]
+    blocking: bool,
+}
+
+impl ScenarioResource {
+    fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+pub struct ScenarioSlot {
+    resource: String,
+    base_amount: u64,
+}
+
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+pub struct ScenarioProcess {
+    name: String,
+    resource_intensity: GenericProcessResourceIntensity,
+    #[serde(default)]
+    slots: Vec<ScenarioSlot>,
+}
+
+/// Everything needed to build a simulation from scratch, in a form meant to be
+/// written by hand. Live state (current amounts, the clock) is not part of it.
+#[derive(Clone, PartialEq, serde::Serialize, serde::Deserialize, Debug)]
+pub struct Scenario {
+    version: u32,
+    #[serde(default)]
+    settings: ScenarioSettings,
+    #[serde(default)]
+    resources: Vec<ScenarioResource>,
+    #[serde(default)]
+    processes: Vec<ScenarioProcess>,
+}
+
+#[derive(Clone, Copy)]
+enum ScenarioFormat {
+    Toml,
+    Json,
+}
+
+impl ScenarioFormat {
+    fn from_path(path: &Path) -> Result<Self, ScenarioError> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+
+        match extension.as_str() {
+            "toml" => Ok(ScenarioFormat::Toml),
+            "json" => Ok(ScenarioFormat::Json),
+            _ => Err(ScenarioError::UnsupportedFormat(extension)),
+        }
+    }
+}
+
+impl Scenario {
+    /// Describes the given state. Resources sharing a name get a key so slots
+    /// still point at the right one.
+    pub fn from_state(
+        settings: ScenarioSettings,
+        processes: &[ProcessStates],
+        resources: &[GenericResource],
+    ) -> Self {
+        let mut names: HashMap<String, usize> = HashMap::new();
+        for resource in resources {
+            *names.entry(resource.name()).or_default() += 1;
+        }
+
+        let mut keys = HashMap::new();
+        let resources = resources
+            .iter()
+            .enumerate()
+            .map(|(index, resource)| {
+                let key = (names[&resource.name()] > 1)
+                    .then(|| format!("{}-{}", resource.name(), index + 1));
+                let scenario_resource = ScenarioResource {
+                    key,
+                    name: resource.name(),
+                    total_amount: resource.total_amount(),
+                    blocking: resource.blocking(),
+                };
+                keys.insert(resource.id(), scenario_resource.key().to_string());
+                scenario_resource
+            })
+            .collect();
+
+        let processes = processes
+            .iter()
+            .map(|process| {
+                let process = process.process();
+                ScenarioProcess {
+                    name: process.name(),
+                    resource_intensity: *process.resource_intensity(),
+                    slots: process
+                        .resource_slot()
+                        .iter()
+                        .filter_map(|slot| {
+                            Some(ScenarioSlot {
+                                resource: keys.get(&slot.resource_id())?.clone(),
+                                base_amount: slot.base_amount(),
+                            })
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Self {
+            version: SCENARIO_SCHEMA.version,
+            settings,
+            resources,
+            processes,
+        }
+    }
+
+    pub fn settings(&self) -> &ScenarioSettings {
+        &self.settings
+    }
+
+    /// Creates fresh resources and ready processes, validating them the same way
+    /// the individual commands do.
+    pub fn build(&self) -> Result<(Vec<GenericResource>, Vec<ProcessStates>), SimulationError> {
+        validate_speed(self.settings.speed)?;
+        validate_time_step(self.settings.time_step)?;
+
+        let mut resources = vec![];
+        let mut by_key = HashMap::new();
+        for resource in &self.resources {
+            validate_name(&resource.name)?;
+            let created = GenericResource::new(
+                resource.name.clone(),
+                resource.total_amount,
+                resource.blocking,
+            );
+            if by_key
+                .insert(resource.key().to_string(), created.clone())
+                .is_some()
+            {
+                return Err(ScenarioError::DuplicateResource(resource.key().to_string()).into());
+            }
+            resources.push(created);
+        }
+
+        let mut processes = vec![];
+        for process in &self.processes {
+            validate_name(&process.name)?;
+            let mut created = Process::new(process.name.clone(), process.resource_intensity);
+
+            for slot in &process.slots {
+                let resource =
+                    by_key
+                        .get(&slot.resource)
+                        .ok_or_else(|| ScenarioError::UnknownResource {
+                            process: process.name.clone(),
+                            resource: slot.resource.clone(),
+                        })?;
+                validate_slot(&created, resource, slot.base_amount)?;
+                created.add_resource(resource, slot.base_amount);
+            }
+
+            processes.push(ProcessStates::Ready(created));
+        }
+
+        Ok((resources, processes))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, ScenarioError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| ScenarioError::Io(e.to_string()))?;
+        Self::parse(path, &contents)
+    }
+
+    /// Like `read`, for callers that already have the contents. `path` only picks the format.
+    pub fn parse(path: &Path, contents: &str) -> Result<Self, ScenarioError> {
+        let format = ScenarioFormat::from_path(path)?;
+
+        // Both formats go through the same JSON value, so migrations are written once.
+        let document: serde_json::Value = match format {
+            ScenarioFormat::Toml => {
+                toml::from_str(contents).map_err(|e| ScenarioError::Parse(e.to_string()))?
+            }
+            ScenarioFormat::Json => {
+                serde_json::from_str(contents).map_err(|e| ScenarioError::Parse(e.to_string()))?
+            }
+        };
+
+        let document = SCENARIO_SCHEMA
+            .upgrade(document)
+            .map_err(ScenarioError::Schema)?;
+        serde_json::from_value(document).map_err(|e| ScenarioError::Parse(e.to_string()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), ScenarioError> {
+        let contents = self.render(path)?;
+        std::fs::write(path, contents).map_err(|e| ScenarioError::Io(e.to_string()))
+    }
+
+    /// The contents `write` would put in the file at `path`.
+    pub fn render(&self, path: &Path) -> Result<String, ScenarioError> {
+        Ok(match ScenarioFormat::from_path(path)? {
+            ScenarioFormat::Toml => {
+                toml::to_string_pretty(self).map_err(|e| ScenarioError::Parse(e.to_string()))?
+            }
+            ScenarioFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| ScenarioError::Parse(e.to_string()))?,
+        })
+    }
+}
+
+/// Replaces the whole simulation with the one described in the file at `path`.
+/// The format is picked from the extension.
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_load_scenario(
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<Snapshot, SimulationError> {
+    let scenario = Scenario::read(Path::new(&path))?;
+
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.load_scenario(&app_handle, &scenario)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_save_scenario(
+    app_handle: tauri::AppHandle,
+    path: String,
+) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    let scenario = sim.0.scenario()?;
+
+    Ok(scenario.write(Path::new(&path))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two resources sharing a name, so the written scenario needs keys.
+    fn scenario() -> Scenario {
+        let small = GenericResource::new("Disk".to_string(), 10, true);
+        let large = GenericResource::new("Disk".to_string(), 500, false);
+        let mut process = Process::new(
+            "Writer".to_string(),
+            GenericProcessResourceIntensity::Medium,
+        );
+        process.add_resource(&small, 3);
+        process.add_resource(&large, 200);
+
+        Scenario::from_state(
+            ScenarioSettings {
+                speed: 2.5,
+                engine: Engine::DiscreteEvent,
+                ..ScenarioSettings::default()
+            },
+            &[ProcessStates::Ready(process)],
+            &[small, large],
+        )
+    }
+
+    #[test]
+    fn scenarios_survive_toml_and_json() {
+        let scenario = scenario();
+        for file in ["scenario.toml", "scenario.json"] {
+            let path = Path::new(file);
+            let parsed = Scenario::parse(path, &scenario.render(path).unwrap()).unwrap();
+            assert_eq!(parsed, scenario);
+        }
+    }
+
+    #[test]
+    fn slots_point_at_the_right_one_of_two_resources_with_the_same_name() {
+        let (resources, processes) = scenario().build().unwrap();
+        let slots = processes[0].process().resource_slot();
+
+        let amounts: Vec<(u64, u64)> = slots
+            .iter()
+            .map(|slot| {
+                let resource = resources.iter().find(|r| r.id() == slot.resource_id());
+                (resource.unwrap().total_amount(), slot.base_amount())
+            })
+            .collect();
+        assert_eq!(amounts, vec![(10, 3), (500, 200)]);
+    }
+
+    #[test]
+    fn slots_need_a_resource_defined_once() {
+        let unknown = r#"
+version = 1
+
+[[processes]]
+name = "Reader"
+resource_intensity = "Low"
+slots = [{ resource = "Memory", base_amount = 1 }]
+"#;
+        let scenario = Scenario::parse(Path::new("unknown.toml"), unknown).unwrap();
+        assert_eq!(
+            scenario.build().unwrap_err(),
+            ScenarioError::UnknownResource {
+                process: "Reader".to_string(),
+                resource: "Memory".to_string(),
+            }
+            .into()
+        );
+
+        let duplicate = r#"
+version = 1
+
+[[resources]]
+name = "Memory"
+total_amount = 10
+
+[[resources]]
+name = "Memory"
+total_amount = 20
+"#;
+        let scenario = Scenario::parse(Path::new("duplicate.toml"), duplicate).unwrap();
+        assert_eq!(
+            scenario.build().unwrap_err(),
+            ScenarioError::DuplicateResource("Memory".to_string()).into()
+        );
+    }
+
+    #[test]
+    fn newer_scenarios_are_refused() {
+        let error = Scenario::parse(Path::new("new.json"), r#"{ "version": 2 }"#).unwrap_err();
+        assert!(matches!(
+            error,
+            ScenarioError::Schema(SchemaError::TooNew { version: 2, .. })
+        ));
+    }
+}
@@ -0,0 +1,208 @@
+use std::time::Duration;
+
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Keeps the deadlines of the worker loop, so it can sleep until the next one
+/// instead of polling. Times are measured from any fixed origin, so the loop can be
+/// driven by a clock other than `Instant`.
+pub struct Scheduler {
+    next_tick: Duration,
+    next_publish: Duration,
+    rate_window: Duration,
+    ticks_in_window: u64,
+    last_rate: f64,
+    speed: f64,
+    /// Something changed since the last publish.
+    dirty: bool,
+}
+
+impl Scheduler {
+    pub fn new(now: Duration) -> Self {
+        Self {
+            next_tick: now,
+            next_publish: now,
+            rate_window: now,
+            ticks_in_window: 0,
+            last_rate: 0.0,
+            speed: 0.0,
+            dirty: true,
+        }
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// `speed` is in ticks per second and may be fractional.
+    pub fn tick_due(&mut self, now: Duration, speed: f64, unbounded: bool) -> bool {
+        if speed <= 0.0 {
+            self.speed = 0.0;
+            return false;
+        }
+
+        // Start counting from now when resuming or changing speed, instead of
+        // catching up on ticks that were never scheduled.
+        if speed != self.speed {
+            if self.speed == 0.0 {
+                self.rate_window = now;
+                self.ticks_in_window = 0;
+            }
+            self.speed = speed;
+            self.next_tick = now;
+        }
+
+        if !unbounded {
+            if now < self.next_tick {
+                return false;
+            }
+
+            let interval = Duration::from_secs_f64(1.0 / speed);
+            self.next_tick += interval;
+            if self.next_tick < now {
+                self.next_tick = now + interval;
+            }
+        }
+
+        self.ticks_in_window += 1;
+        self.dirty = true;
+        true
+    }
+
+    pub fn publish_due(&mut self, now: Duration, publish_rate: u64) -> bool {
+        if !self.dirty || now < self.next_publish {
+            return false;
+        }
+
+        self.next_publish = now + Duration::from_secs_f64(1.0 / publish_rate.max(1) as f64);
+        self.dirty = false;
+        true
+    }
+
+    /// Returns the achieved tick rate once per window. While paused it is reported
+    /// once as 0 and then stays quiet.
+    pub fn rate_due(&mut self, now: Duration) -> Option<f64> {
+        if !self.measuring_rate() || now < self.rate_window + RATE_WINDOW {
+            return None;
+        }
+
+        let rate = self.ticks_in_window as f64 / (now - self.rate_window).as_secs_f64();
+        self.rate_window = now;
+        self.ticks_in_window = 0;
+        self.last_rate = rate;
+        Some(rate)
+    }
+
+    /// Next instant the worker has to wake up on its own, or `None` when only a
+    /// command can give it something to do.
+    pub fn next_wake(&self, now: Duration, unbounded: bool) -> Option<Duration> {
+        let mut deadlines = vec![];
+
+        if self.speed > 0.0 {
+            if unbounded {
+                return Some(now);
+            }
+            deadlines.push(self.next_tick);
+        }
+
+        if self.dirty {
+            deadlines.push(self.next_publish);
+        }
+
+        if self.measuring_rate() {
+            deadlines.push(self.rate_window + RATE_WINDOW);
+        }
+
+        deadlines.into_iter().min()
+    }
+
+    fn measuring_rate(&self) -> bool {
+        self.speed > 0.0 || self.last_rate != 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn publishes_follow_their_own_rate_and_only_after_changes() {
+        let mut scheduler = Scheduler::new(ms(0));
+        assert!(scheduler.publish_due(ms(0), 10));
+        assert!(!scheduler.publish_due(ms(50), 10));
+
+        // Ticks far faster than the publish rate still publish at most every 100ms.
+        let mut publishes = 0;
+        for now in 0..=1000 {
+            scheduler.tick_due(ms(now), 1000.0, false);
+            if scheduler.publish_due(ms(now), 10) {
+                publishes += 1;
+            }
+        }
+        assert_eq!(publishes, 10);
+
+        // Nothing changed since the last publish.
+        assert!(!scheduler.publish_due(ms(5000), 10));
+        scheduler.mark_dirty();
+        assert!(scheduler.publish_due(ms(5000), 10));
+    }
+
+    #[test]
+    fn ticks_are_paced_by_the_speed() {
+        let mut scheduler = Scheduler::new(ms(0));
+        let ticks = (0..1000)
+            .filter(|now| scheduler.tick_due(ms(*now), 4.0, false))
+            .count();
+        assert_eq!(ticks, 4);
+        assert!(scheduler.publish_due(ms(999), 10));
+        assert_eq!(scheduler.next_wake(ms(999), false), Some(ms(1000)));
+    }
+
+    #[test]
+    fn resuming_does_not_catch_up_on_missed_ticks() {
+        let mut scheduler = Scheduler::new(ms(0));
+        assert!(scheduler.tick_due(ms(0), 10.0, false));
+        assert!(!scheduler.tick_due(ms(10), 0.0, false));
+
+        assert!(scheduler.tick_due(ms(5000), 10.0, false));
+        assert!(!scheduler.tick_due(ms(5001), 10.0, false));
+        assert!(scheduler.tick_due(ms(5100), 10.0, false));
+    }
+
+    #[test]
+    fn a_paused_worker_sleeps_until_woken() {
+        let mut scheduler = Scheduler::new(ms(0));
+        for now in 0..1000 {
+            scheduler.tick_due(ms(now), 10.0, false);
+        }
+        assert_eq!(scheduler.rate_due(ms(1000)), Some(10.0));
+        assert!(scheduler.publish_due(ms(1000), 10));
+        assert!(!scheduler.tick_due(ms(1000), 0.0, false));
+
+        // One last rate of 0 is still due, then there is nothing left to wait for.
+        assert_eq!(scheduler.next_wake(ms(1000), false), Some(ms(2000)));
+        assert_eq!(scheduler.rate_due(ms(2000)), Some(0.0));
+        assert_eq!(scheduler.next_wake(ms(2000), false), None);
+        assert_eq!(scheduler.next_wake(ms(2000), true), None);
+    }
+
+    #[test]
+    fn unbounded_ticks_never_wait() {
+        let mut scheduler = Scheduler::new(ms(0));
+        assert!((0..100).all(|_| scheduler.tick_due(ms(0), 1.0, true)));
+        assert_eq!(scheduler.next_wake(ms(0), true), Some(ms(0)));
+        assert_eq!(scheduler.rate_due(ms(1000)), Some(100.0));
+    }
+
+    #[test]
+    fn fractional_speeds_tick_less_than_once_a_second() {
+        let mut scheduler = Scheduler::new(ms(0));
+        let ticks: Vec<u64> = (0..5000)
+            .filter(|now| scheduler.tick_due(ms(*now), 0.5, false))
+            .collect();
+        assert_eq!(ticks, vec![0, 2000, 4000]);
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt;
+
+use serde_json::Value;
+
+/// Upgrades a document in place by exactly one version.
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Versioning of one kind of persisted document.
+///
+/// To change a persisted type, bump `version` and append a migration that turns a
+/// document of the previous version into the new shape. Old files then go through
+/// every migration after their own version before being deserialized.
+pub struct Schema {
+    pub name: &'static str,
+    pub version: u32,
+    /// `migrations[n]` upgrades version `n + 1` to `n + 2`.
+    pub migrations: &'static [Migration],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaError {
+    MissingVersion {
+        schema: &'static str,
+    },
+    TooOld {
+        schema: &'static str,
+        version: u64,
+    },
+    TooNew {
+        schema: &'static str,
+        version: u64,
+        current: u32,
+    },
+    MigrationFailed {
+        schema: &'static str,
+        from: u32,
+        reason: String,
+    },
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::MissingVersion { schema } => {
+                write!(f, "The {} has no numeric version field", schema)
+            }
+            SchemaError::TooOld { schema, version } => {
+                write!(f, "The {} version {} is not supported", schema, version)
+            }
+            SchemaError::TooNew {
+                schema,
+                version,
+                current,
+            } => write!(
+                f,
+                "The {} version {} is newer than the supported version {}, update the app to open it",
+                schema, version, current
+            ),
+            SchemaError::MigrationFailed {
+                schema,
+                from,
+                reason,
+            } => write!(
+                f,
+                "Could not upgrade the {} from version {}: {}",
+                schema, from, reason
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl Schema {
+    /// Runs every migration between the document's version and the current one.
+    pub fn upgrade(&self, mut document: Value) -> Result<Value, SchemaError> {
+        debug_assert_eq!(self.migrations.len() + 1, self.version as usize);
+
+        let version = document
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or(SchemaError::MissingVersion { schema: self.name })?;
+
+        if version == 0 {
+            return Err(SchemaError::TooOld {
+                schema: self.name,
+                version,
+            });
+        }
+        if version > self.version as u64 {
+            return Err(SchemaError::TooNew {
+                schema: self.name,
+                version,
+                current: self.version,
+            });
+        }
+
+        for (index, migration) in self
+            .migrations
+            .iter()
+            .enumerate()
+            .skip(version as usize - 1)
+        {
+            let from = index as u32 + 1;
+            migration(&mut document).map_err(|reason| SchemaError::MigrationFailed {
+                schema: self.name,
+                from,
+                reason,
+            })?;
+            document["version"] = Value::from(from + 1);
+        }
+
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Version 2 renamed `amount` to `total_amount`.
+    fn rename_amount(document: &mut Value) -> Result<(), String> {
+        let amount = document
+            .as_object_mut()
+            .and_then(|object| object.remove("amount"))
+            .ok_or("missing amount")?;
+        document["total_amount"] = amount;
+        Ok(())
+    }
+
+    /// Version 3 added a name.
+    fn add_name(document: &mut Value) -> Result<(), String> {
+        document["name"] = Value::from("unnamed");
+        Ok(())
+    }
+
+    const TEST_SCHEMA: Schema = Schema {
+        name: "test document",
+        version: 3,
+        migrations: &[rename_amount, add_name],
+    };
+
+    #[test]
+    fn old_documents_go_through_every_later_migration() {
+        let upgraded = TEST_SCHEMA
+            .upgrade(json!({ "version": 1, "amount": 5 }))
+            .unwrap();
+        assert_eq!(
+            upgraded,
+            json!({ "version": 3, "total_amount": 5, "name": "unnamed" })
+        );
+
+        let upgraded = TEST_SCHEMA
+            .upgrade(json!({ "version": 2, "total_amount": 5 }))
+            .unwrap();
+        assert_eq!(
+            upgraded,
+            json!({ "version": 3, "total_amount": 5, "name": "unnamed" })
+        );
+    }
+
+    #[test]
+    fn current_documents_are_left_alone() {
+        let document = json!({ "version": 3, "total_amount": 5, "name": "cpu" });
+        assert_eq!(TEST_SCHEMA.upgrade(document.clone()).unwrap(), document);
+    }
+
+    #[test]
+    fn versions_outside_the_supported_range_are_refused() {
+        assert_eq!(
+            TEST_SCHEMA.upgrade(json!({ "version": 0 })).unwrap_err(),
+            SchemaError::TooOld {
+                schema: "test document",
+                version: 0,
+            }
+        );
+        assert_eq!(
+            TEST_SCHEMA.upgrade(json!({ "version": 4 })).unwrap_err(),
+            SchemaError::TooNew {
+                schema: "test document",
+                version: 4,
+                current: 3,
+            }
+        );
+        assert_eq!(
+            TEST_SCHEMA.upgrade(json!({ "version": "1" })).unwrap_err(),
+            SchemaError::MissingVersion {
+                schema: "test document",
+            }
+        );
+    }
+
+    #[test]
+    fn failed_migrations_name_the_version_they_started_from() {
+        assert_eq!(
+            TEST_SCHEMA.upgrade(json!({ "version": 1 })).unwrap_err(),
+            SchemaError::MigrationFailed {
+                schema: "test document",
+                from: 1,
+                reason: "missing amount".to_string(),
+            }
+        );
+    }
+}
@@ -1,55 +1,317 @@
 #![allow(unused_imports, unused_variables, dead_code, unreachable_code)]
+#[cfg(feature = "server")]
+use crate::api::ApiError;
+use crate::clock::{SimulationTime, VirtualClock};
+use crate::discrete::{self, DiscreteEngine, Engine};
+use crate::environment::EnvironmentError;
+use crate::events::{EventBus, EventSink, SimulationEvent};
 use crate::generic_process::{Process, ProcessStates};
+use crate::procfs::SystemError;
+use crate::save_state::{SaveState, SaveStateError, SAVE_STATE_SCHEMA};
+use crate::scenario::{Scenario, ScenarioError, ScenarioSettings};
+use crate::scheduler::Scheduler;
+use crate::snapshot::{DeltaTracker, Published, Snapshot};
+use crate::validation::{
+    validate_name, validate_speed, validate_time_step, validate_total_amount, ValidationError,
+};
+#[cfg(feature = "desktop")]
+use crate::TauriSim;
 use crate::{generic_process::GenericProcessResourceIntensity, generic_resource::GenericResource};
-use crate::{AllProcessTraits, ReadyProcess, TauriSim};
+use crate::{AllProcessTraits, ReadyProcess};
+use rand::SeedableRng;
 use std::borrow::BorrowMut;
-use std::os::windows::process;
-use std::sync::mpsc::channel;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{channel, Receiver};
 use std::{
-    sync::{Arc, Mutex},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
     thread,
     time::{Duration, Instant},
 };
+#[cfg(feature = "desktop")]
 use tauri::{AppHandle, Emitter};
+#[cfg(feature = "desktop")]
 use tauri::{Builder, Manager};
 
 extern crate nalgebra as na;
 
+/// Seedable rng every random roll of the simulation goes through, so saving it
+/// is enough to replay the same rolls.
+pub type SimulationRng = rand_chacha::ChaCha8Rng;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    Poisoned,
+    WorkerDead,
+    NoCheckpoint,
+    ProcessNotFound,
+    ResourceNotFound,
+    SlotNotFound,
+    ResourceInUse(Vec<String>),
+    Invalid(ValidationError),
+    Scenario(ScenarioError),
+    SaveState(SaveStateError),
+    Environment(EnvironmentError),
+    System(SystemError),
+    #[cfg(feature = "server")]
+    Api(ApiError),
+    BatchFailed {
+        index: usize,
+        error: Box<SimulationError>,
+    },
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::Poisoned => write!(f, "Simulation state is poisoned"),
+            SimulationError::WorkerDead => write!(f, "Simulation worker is not running"),
+            SimulationError::NoCheckpoint => {
+                write!(f, "Nothing to recover, the simulation has not run yet")
+            }
+            SimulationError::ProcessNotFound => write!(f, "Process not found"),
+            SimulationError::ResourceNotFound => write!(f, "Resource not found"),
+            SimulationError::SlotNotFound => write!(f, "Resource slot not found"),
+            SimulationError::ResourceInUse(process_ids) => {
+                write!(
+                    f,
+                    "Resource is held by processes: {}",
+                    process_ids.join(", ")
+                )
+            }
+            SimulationError::Invalid(error) => write!(f, "{}", error),
+            SimulationError::Scenario(error) => write!(f, "{}", error),
+            SimulationError::SaveState(error) => write!(f, "{}", error),
+            SimulationError::Environment(error) => write!(f, "{}", error),
+            SimulationError::System(error) => write!(f, "{}", error),
+            #[cfg(feature = "server")]
+            SimulationError::Api(error) => write!(f, "{}", error),
+            SimulationError::BatchFailed { index, error } => {
+                write!(f, "Mutation {} failed: {}", index, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+// The frontend only shows the message, so errors cross the IPC boundary as strings.
+impl serde::Serialize for SimulationError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl From<ValidationError> for SimulationError {
+    fn from(error: ValidationError) -> Self {
+        SimulationError::Invalid(error)
+    }
+}
+
+impl From<ScenarioError> for SimulationError {
+    fn from(error: ScenarioError) -> Self {
+        SimulationError::Scenario(error)
+    }
+}
+
+impl From<SaveStateError> for SimulationError {
+    fn from(error: SaveStateError) -> Self {
+        SimulationError::SaveState(error)
+    }
+}
+
+impl From<EnvironmentError> for SimulationError {
+    fn from(error: EnvironmentError) -> Self {
+        SimulationError::Environment(error)
+    }
+}
+
+impl From<SystemError> for SimulationError {
+    fn from(error: SystemError) -> Self {
+        SimulationError::System(error)
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<ApiError> for SimulationError {
+    fn from(error: ApiError) -> Self {
+        SimulationError::Api(error)
+    }
+}
+
+impl<T> From<PoisonError<T>> for SimulationError {
+    fn from(_: PoisonError<T>) -> Self {
+        SimulationError::Poisoned
+    }
+}
+
+/// Locks `mutex`, reporting a poisoned lock as an error instead of panicking.
+pub fn lock_state<T>(mutex: &Mutex<T>) -> Result<MutexGuard<'_, T>, SimulationError> {
+    Ok(mutex.lock()?)
+}
+
+/// Locks `mutex` even if it is poisoned, clearing the poison flag.
+/// Only used by the recovery path, which overwrites the guarded data afterwards.
+fn lock_and_clear<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| {
+        mutex.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Last state the worker published without panicking.
+#[derive(Clone)]
+pub struct Checkpoint {
+    time: SimulationTime,
+    processes: Vec<ProcessStates>,
+    resources: Vec<GenericResource>,
+    discrete: DiscreteEngine,
+    rng: Option<SimulationRng>,
+}
+
+/// What to do with the slots that still point to a resource being removed.
+#[derive(Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize, Debug, Default)]
+pub enum ResourceRemovalPolicy {
+    /// Fail with `SimulationError::ResourceInUse` while any process holds a slot for it.
+    #[default]
+    Refuse,
+    /// Drop the dependent slots and keep the processes.
+    ReleaseSlots,
+    /// Drop every process holding a slot for the resource.
+    DeleteProcesses,
+}
+
+#[derive(Clone, serde::Serialize, Debug, Default)]
+pub struct ResourceRemoval {
+    released_slots: Vec<String>,
+    released_processes: Vec<String>,
+    deleted_processes: Vec<String>,
+}
+
+#[derive(Clone, serde::Serialize, Debug)]
+pub struct SimulationHealth {
+    worker_alive: bool,
+    poisoned: bool,
+}
+
+impl SimulationHealth {
+    /// For hosts that call `pump` themselves, where there is no worker thread to ask.
+    pub fn pumped(self, alive: bool) -> Self {
+        SimulationHealth {
+            worker_alive: alive,
+            ..self
+        }
+    }
+}
+
 pub struct _Simulation {
-    simulation_speed: Arc<Mutex<u64>>,
-    last_simulation_speed: Arc<Mutex<u64>>,
+    simulation_speed: Arc<Mutex<f64>>,
+    last_simulation_speed: Arc<Mutex<f64>>,
     processes: Arc<Mutex<Vec<ProcessStates>>>,
     resources: Arc<Mutex<Vec<GenericResource>>>,
     tx: std::sync::mpsc::Sender<()>,
     rx: std::sync::mpsc::Receiver<()>,
 }
 
+/// What the worker loop keeps between iterations: its deadlines and the allocations
+/// waiting for the next publish.
+pub struct Pump {
+    scheduler: Scheduler,
+    allocations: HashMap<String, SimulationEvent>,
+}
+
+impl Pump {
+    pub fn new() -> Self {
+        Pump {
+            scheduler: Scheduler::new(Duration::ZERO),
+            allocations: HashMap::new(),
+        }
+    }
+}
+
+impl Default for Pump {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Every lock below is taken in this order, skipping the ones that are not needed:
+///
+/// 1. `simulation_speed`, `last_simulation_speed`
+/// 2. `resources`, `processes`
+/// 3. `discrete`, `rng`
+/// 4. `engine`, `publish_rate`, `unbounded`, `delta`, `checkpoint`, `worker`
+/// 5. `clock`, which `EventBus::emit` also takes, so events can be sent while holding
+///    any of the others but not the clock itself.
+///
+/// Never take a lock while holding one that comes after it. `rx` and
+/// `achieved_tick_rate` are only ever held on their own.
 #[derive(Clone)]
 pub struct RunningSimulation {
-    simulation_speed: Arc<Mutex<u64>>,
-    last_simulation_speed: Arc<Mutex<u64>>,
+    simulation_speed: Arc<Mutex<f64>>,
+    last_simulation_speed: Arc<Mutex<f64>>,
     processes: Arc<Mutex<Vec<ProcessStates>>>,
     resources: Arc<Mutex<Vec<GenericResource>>>,
+    /// Wakes the worker up when a command changes something it should react to.
     tx: std::sync::mpsc::Sender<()>,
+    rx: Arc<Mutex<Receiver<()>>>,
+    /// `None` until the first publish.
+    checkpoint: Arc<Mutex<Option<Checkpoint>>>,
+    worker: Arc<Mutex<Option<thread::JoinHandle<()>>>>,
+    events: EventBus,
+    delta: Arc<Mutex<DeltaTracker>>,
+    /// Ignore `simulation_speed` and tick as fast as possible while it is not 0.
+    unbounded: Arc<Mutex<bool>>,
+    /// Snapshots published per second, independent of the tick rate.
+    publish_rate: Arc<Mutex<u64>>,
+    /// Ticks actually run during the last second.
+    achieved_tick_rate: Arc<Mutex<f64>>,
+    clock: Arc<Mutex<VirtualClock>>,
+    engine: Arc<Mutex<Engine>>,
+    discrete: Arc<Mutex<DiscreteEngine>>,
+    rng: Arc<Mutex<SimulationRng>>,
 }
 
 impl RunningSimulation {
     pub fn new() -> Self {
         let (tx, rx) = channel();
+        let clock = Arc::new(Mutex::new(VirtualClock::new()));
         RunningSimulation {
-            simulation_speed: Arc::new(Mutex::new(60)),
-            last_simulation_speed: Arc::new(Mutex::new(0)),
+            simulation_speed: Arc::new(Mutex::new(60.0)),
+            last_simulation_speed: Arc::new(Mutex::new(0.0)),
             processes: Arc::new(Mutex::new(vec![])),
             resources: Arc::new(Mutex::new(vec![])),
             tx,
+            rx: Arc::new(Mutex::new(rx)),
+            checkpoint: Arc::new(Mutex::new(None)),
+            worker: Arc::new(Mutex::new(None)),
+            events: EventBus::new(Arc::clone(&clock)),
+            delta: Arc::new(Mutex::new(DeltaTracker::new())),
+            unbounded: Arc::new(Mutex::new(false)),
+            publish_rate: Arc::new(Mutex::new(30)),
+            achieved_tick_rate: Arc::new(Mutex::new(0.0)),
+            clock,
+            engine: Arc::new(Mutex::new(Engine::default())),
+            discrete: Arc::new(Mutex::new(DiscreteEngine::new())),
+            rng: Arc::new(Mutex::new(SimulationRng::from_entropy())),
         }
     }
 }
 
+impl Default for RunningSimulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone)]
 pub struct StoppedSimulation {
-    simulation_speed: Arc<Mutex<u64>>,
-    last_simulation_speed: Arc<Mutex<u64>>,
+    simulation_speed: Arc<Mutex<f64>>,
+    last_simulation_speed: Arc<Mutex<f64>>,
     processes: Arc<Mutex<Vec<ProcessStates>>>,
     resources: Arc<Mutex<Vec<GenericResource>>>,
 }
@@ -62,8 +324,8 @@ pub enum Simulation {
 impl Simulation {
     pub fn new() -> StoppedSimulation {
         StoppedSimulation {
-            simulation_speed: Arc::new(Mutex::new(60)),
-            last_simulation_speed: Arc::new(Mutex::new(0)),
+            simulation_speed: Arc::new(Mutex::new(60.0)),
+            last_simulation_speed: Arc::new(Mutex::new(0.0)),
             processes: Arc::new(Mutex::new(vec![])),
             resources: Arc::new(Mutex::new(vec![])),
         }
@@ -71,29 +333,34 @@ impl Simulation {
 }
 
 pub trait AllSimulationTrait {
-    fn add_process(&mut self, process: ProcessStates);
-    fn remove_process(&mut self, process: &ProcessStates);
+    fn add_process(&mut self, process: ProcessStates) -> Result<(), SimulationError>;
+    fn remove_process(&mut self, process: &ProcessStates) -> Result<(), SimulationError>;
     fn processes(&self) -> Arc<Mutex<Vec<ProcessStates>>>;
-    fn get_process_by_id(&self, id: String) -> Option<ProcessStates>;
-
-    fn add_resource(&mut self, resource: GenericResource);
-    fn remove_resource(&mut self, resource: &GenericResource);
+    fn get_process_by_id(&self, id: String) -> Result<Option<ProcessStates>, SimulationError>;
+
+    fn add_resource(&mut self, resource: GenericResource) -> Result<(), SimulationError>;
+    fn remove_resource(&mut self, resource: &GenericResource) -> Result<(), SimulationError>;
+    fn remove_resource_by_id(
+        &mut self,
+        resource_id: String,
+        policy: ResourceRemovalPolicy,
+    ) -> Result<ResourceRemoval, SimulationError>;
     fn resources(&self) -> Arc<Mutex<Vec<GenericResource>>>;
-    fn get_resource_by_id(&self, id: String) -> Option<GenericResource>;
+    fn get_resource_by_id(&self, id: String) -> Result<Option<GenericResource>, SimulationError>;
 
-    fn set_simulation_speed(&mut self, speed: u64);
-    fn simulation_speed(&self) -> Arc<Mutex<u64>>;
+    fn set_simulation_speed(&mut self, speed: f64) -> Result<(), SimulationError>;
+    fn simulation_speed(&self) -> Arc<Mutex<f64>>;
 }
 
 impl AllSimulationTrait for Simulation {
-    fn add_process(&mut self, process: ProcessStates) {
+    fn add_process(&mut self, process: ProcessStates) -> Result<(), SimulationError> {
         match self {
             Simulation::Running(sim) => sim.add_process(process),
             Simulation::Stopped(sim) => sim.add_process(process),
         }
     }
 
-    fn remove_process(&mut self, process: &ProcessStates) {
+    fn remove_process(&mut self, process: &ProcessStates) -> Result<(), SimulationError> {
         match self {
             Simulation::Running(sim) => sim.remove_process(process),
             Simulation::Stopped(sim) => sim.remove_process(process),
@@ -107,20 +374,31 @@ impl AllSimulationTrait for Simulation {
         }
     }
 
-    fn add_resource(&mut self, resource: GenericResource) {
+    fn add_resource(&mut self, resource: GenericResource) -> Result<(), SimulationError> {
         match self {
             Simulation::Running(sim) => sim.add_resource(resource),
             Simulation::Stopped(sim) => sim.add_resource(resource),
         }
     }
 
-    fn remove_resource(&mut self, resource: &GenericResource) {
+    fn remove_resource(&mut self, resource: &GenericResource) -> Result<(), SimulationError> {
         match self {
             Simulation::Running(sim) => sim.remove_resource(resource),
             Simulation::Stopped(sim) => sim.remove_resource(resource),
         }
     }
 
+    fn remove_resource_by_id(
+        &mut self,
+        resource_id: String,
+        policy: ResourceRemovalPolicy,
+    ) -> Result<ResourceRemoval, SimulationError> {
+        match self {
+            Simulation::Running(sim) => sim.remove_resource_by_id(resource_id, policy),
+            Simulation::Stopped(sim) => sim.remove_resource_by_id(resource_id, policy),
+        }
+    }
+
     fn resources(&self) -> Arc<Mutex<Vec<GenericResource>>> {
         match self {
             Simulation::Running(sim) => sim.resources(),
@@ -128,36 +406,36 @@ impl AllSimulationTrait for Simulation {
         }
     }
 
-    fn set_simulation_speed(&mut self, speed: u64) {
+    fn set_simulation_speed(&mut self, speed: f64) -> Result<(), SimulationError> {
         match self {
             Simulation::Running(sim) => sim.set_simulation_speed(speed),
             Simulation::Stopped(sim) => sim.set_simulation_speed(speed),
         }
     }
 
-    fn simulation_speed(&self) -> Arc<Mutex<u64>> {
+    fn simulation_speed(&self) -> Arc<Mutex<f64>> {
         match self {
             Simulation::Running(sim) => sim.simulation_speed(),
             Simulation::Stopped(sim) => sim.simulation_speed(),
         }
     }
 
-    fn get_process_by_id(&self, id: String) -> Option<ProcessStates> {
+    fn get_process_by_id(&self, id: String) -> Result<Option<ProcessStates>, SimulationError> {
         let binding = self.processes();
-        let processes = binding.lock().unwrap();
+        let processes = lock_state(&binding)?;
         let process = processes.iter().find(|p| match p {
             ProcessStates::Ready(ready_process) => ready_process.id() == id,
             ProcessStates::Blocked(blocked_process) => blocked_process.id() == id,
             ProcessStates::Working(working_process) => working_process.id() == id,
         });
-        process.cloned()
+        Ok(process.cloned())
     }
 
-    fn get_resource_by_id(&self, id: String) -> Option<GenericResource> {
+    fn get_resource_by_id(&self, id: String) -> Result<Option<GenericResource>, SimulationError> {
         let binding = self.resources();
-        let resources = binding.lock().unwrap();
+        let resources = lock_state(&binding)?;
         let resource = resources.iter().find(|r| r.id() == id);
-        resource.cloned()
+        Ok(resource.cloned())
     }
 }
 
@@ -206,72 +484,131 @@ impl AllSimulationTrait for Simulation {
 //     }
 // }
 
+/// Removes a resource from `resources`, applying `policy` to the slots in `processes`
+/// that still point to it.
+pub fn remove_resource_from(
+    resources: &mut Vec<GenericResource>,
+    processes: &mut Vec<ProcessStates>,
+    resource_id: &str,
+    policy: ResourceRemovalPolicy,
+) -> Result<ResourceRemoval, SimulationError> {
+    let index = resources
+        .iter()
+        .position(|r| r.id() == resource_id)
+        .ok_or(SimulationError::ResourceNotFound)?;
+
+    let holders = processes
+        .iter()
+        .filter(|p| {
+            p.process()
+                .resource_slot()
+                .iter()
+                .any(|slot| slot.resource_id() == resource_id)
+        })
+        .map(|p| p.id())
+        .collect::<Vec<String>>();
+
+    let mut removal = ResourceRemoval::default();
+    match policy {
+        ResourceRemovalPolicy::Refuse => {
+            if !holders.is_empty() {
+                return Err(SimulationError::ResourceInUse(holders));
+            }
+        }
+        ResourceRemovalPolicy::ReleaseSlots => {
+            for process in processes.iter_mut() {
+                process.process_mut().resource_slot_mut().retain(|slot| {
+                    if slot.resource_id() != resource_id {
+                        return true;
+                    }
+                    removal.released_slots.push(slot.id());
+                    false
+                });
+            }
+            removal.released_processes = holders;
+        }
+        ResourceRemovalPolicy::DeleteProcesses => {
+            processes.retain(|p| !holders.contains(&p.id()));
+            removal.deleted_processes = holders;
+        }
+    }
+
+    resources.remove(index);
+    Ok(removal)
+}
+
 macro_rules! impl_AllSimulationTrait {
     (for $($t:ty),+) => {
         $(impl  AllSimulationTrait  for $t {
-            fn add_process(&mut self, process: ProcessStates) {
-                self.processes().lock().unwrap().push(process);
+            fn add_process(&mut self, process: ProcessStates) -> Result<(), SimulationError> {
+                lock_state(&self.processes)?.push(process);
+                Ok(())
             }
             fn processes(&self) -> Arc<Mutex<Vec<ProcessStates>>> {
                 Arc::clone(&self.processes)
             }
-            fn add_resource(&mut self, resource: GenericResource) {
-                self.resources().lock().unwrap().push(resource);
+            fn add_resource(&mut self, resource: GenericResource) -> Result<(), SimulationError> {
+                lock_state(&self.resources)?.push(resource);
+                Ok(())
             }
             fn resources(&self) -> Arc<Mutex<Vec<GenericResource>>> {
                 Arc::clone(&self.resources)
             }
-            fn set_simulation_speed(&mut self, speed: u64) {
-                let simulation_speed = self.simulation_speed();
-                let mut simulation_speed = simulation_speed.lock().unwrap();
-                *simulation_speed = speed;
+            fn set_simulation_speed(&mut self, speed: f64) -> Result<(), SimulationError> {
+                validate_speed(speed)?;
+                *lock_state(&self.simulation_speed)? = speed;
+                Ok(())
             }
-            fn simulation_speed(&self) -> Arc<Mutex<u64>> {
+            fn simulation_speed(&self) -> Arc<Mutex<f64>> {
                 Arc::clone(&self.simulation_speed)
             }
 
-            fn remove_process(&mut self, process: &ProcessStates) {
-                let index = self
-                    .processes()
-                    .lock()
-                    .unwrap()
-                    .iter()
-                    .position(|p| *p == *process);
+            fn remove_process(&mut self, process: &ProcessStates) -> Result<(), SimulationError> {
+                let mut processes = lock_state(&self.processes)?;
+                let index = processes.iter().position(|p| *p == *process);
 
                 if let Some(index) = index {
-                    self.processes().lock().unwrap().remove(index);
+                    processes.remove(index);
                 }
+                Ok(())
             }
 
-            fn remove_resource(&mut self, resource: &GenericResource) {
-                let index = self
-                    .resources()
-                    .lock()
-                    .unwrap()
-                    .iter()
-                    .position(|r| *r == *resource);
+            fn remove_resource(&mut self, resource: &GenericResource) -> Result<(), SimulationError> {
+                let mut resources = lock_state(&self.resources)?;
+                let index = resources.iter().position(|r| r.id() == resource.id());
 
                 if let Some(index) = index {
-                    self.resources().lock().unwrap().remove(index);
+                    resources.remove(index);
                 }
+                Ok(())
+            }
+
+            fn remove_resource_by_id(
+                &mut self,
+                resource_id: String,
+                policy: ResourceRemovalPolicy,
+            ) -> Result<ResourceRemoval, SimulationError> {
+                // Resources before processes, see the lock order on `RunningSimulation`.
+                let mut resources = lock_state(&self.resources)?;
+                let mut processes = lock_state(&self.processes)?;
+
+                remove_resource_from(&mut resources, &mut processes, &resource_id, policy)
             }
 
-            fn get_process_by_id(&self, id: String) -> Option<ProcessStates> {
-                let binding = self.processes();
-                let processes = binding.lock().unwrap();
+            fn get_process_by_id(&self, id: String) -> Result<Option<ProcessStates>, SimulationError> {
+                let processes = lock_state(&self.processes)?;
                 let process = processes.iter().find(|p| match p {
                     ProcessStates::Ready(ready_process) => ready_process.id() == id,
                     ProcessStates::Blocked(blocked_process) => blocked_process.id() == id,
                     ProcessStates::Working(working_process) => working_process.id() == id,
                 });
-                process.cloned()
+                Ok(process.cloned())
             }
 
-            fn get_resource_by_id(&self, id: String) -> Option<GenericResource> {
-                let binding = self.resources();
-                let resources = binding.lock().unwrap();
+            fn get_resource_by_id(&self, id: String) -> Result<Option<GenericResource>, SimulationError> {
+                let resources = lock_state(&self.resources)?;
                 let resource = resources.iter().find(|r| r.id() == id);
-                resource.cloned()
+                Ok(resource.cloned())
             }
 
         })*
@@ -280,93 +617,238 @@ macro_rules! impl_AllSimulationTrait {
 
 impl_AllSimulationTrait!(for _Simulation , RunningSimulation , StoppedSimulation );
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
 pub fn simulation_add_process(
     app_handle: tauri::AppHandle,
     name: String,
     resource_intensity: GenericProcessResourceIntensity,
-) {
+) -> Result<(), SimulationError> {
+    validate_name(&name)?;
+
     let state = app_handle.state::<Mutex<TauriSim>>();
     let new_process = Process::new(name, resource_intensity);
     let new_process = ProcessStates::Ready(new_process);
 
-    let mut sim = state.lock().unwrap();
-    sim.0.add_process(new_process.clone());
+    let mut sim = lock_state(&state)?;
+    sim.0.add_process(new_process.clone())?;
+    sim.0.wake();
+    Ok(())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_processes(app_handle: tauri::AppHandle) -> Vec<ProcessStates> {
+pub fn simulation_processes(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<ProcessStates>, SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
     let sim = &sim.0;
 
     let binding = sim.processes();
-    let processes = binding.lock().unwrap();
-    processes.clone()
+    let processes = lock_state(&binding)?;
+    Ok(processes.clone())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_add_resource(app_handle: tauri::AppHandle, resource: GenericResource) {
+pub fn simulation_add_resource(
+    app_handle: tauri::AppHandle,
+    mut resource: GenericResource,
+) -> Result<(), SimulationError> {
+    validate_name(&resource.name())?;
+    // Resources come from the frontend, so never trust a free amount above the total.
+    resource.set_free_amount(resource.free_amount().min(resource.total_amount()));
+
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let mut sim = state.lock().unwrap();
-    sim.0.add_resource(resource.clone());
+    let mut sim = lock_state(&state)?;
+    sim.0.add_resource(resource.clone())?;
+    sim.0.wake();
+    Ok(())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_remove_process(app_handle: tauri::AppHandle, process_id: String) {
+pub fn simulation_remove_process(
+    app_handle: tauri::AppHandle,
+    process_id: String,
+) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let mut sim = state.lock().unwrap();
+    let mut sim = lock_state(&state)?;
 
-    let process = match sim.0.get_process_by_id(process_id) {
+    let process = match sim.0.get_process_by_id(process_id)? {
         Some(p) => p,
-        None => return,
+        None => return Ok(()),
     };
 
-    sim.0.remove_process(&process);
+    sim.0.remove_process(&process)?;
+    sim.0.wake();
+    Ok(())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_remove_resource(app_handle: tauri::AppHandle, resource_id: String) {
+pub fn simulation_remove_resource(
+    app_handle: tauri::AppHandle,
+    resource_id: String,
+    policy: Option<ResourceRemovalPolicy>,
+) -> Result<ResourceRemoval, SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let mut sim = state.lock().unwrap();
-    let resource = match sim.0.get_resource_by_id(resource_id) {
-        Some(r) => r,
-        None => return,
-    };
-    sim.0.remove_resource(&resource);
+    let mut sim = lock_state(&state)?;
+    let removal = sim
+        .0
+        .remove_resource_by_id(resource_id, policy.unwrap_or_default())?;
+    sim.0.wake();
+    Ok(removal)
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_resources(app_handle: tauri::AppHandle) -> Vec<GenericResource> {
+pub fn simulation_resources(
+    app_handle: tauri::AppHandle,
+) -> Result<Vec<GenericResource>, SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
     let sim = &sim.0;
 
     let binding = sim.resources();
-    let resources = binding.lock().unwrap();
-    resources.clone()
+    let resources = lock_state(&binding)?;
+    Ok(resources.clone())
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_set_resource_total_amount(
+    app_handle: tauri::AppHandle,
+    resource_id: String,
+    total_amount: u64,
+) -> Result<GenericResource, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    let resources = sim.0.resources();
+    let processes = sim.0.processes();
+
+    let mut resources = lock_state(&resources)?;
+    let processes = lock_state(&processes)?;
+
+    let resource = resources
+        .iter_mut()
+        .find(|r| r.id() == resource_id)
+        .ok_or(SimulationError::ResourceNotFound)?;
+
+    validate_total_amount(resource, total_amount, &processes)?;
+    resource.set_total_amount(total_amount);
+    sim.0.wake();
+
+    Ok(resource.clone())
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_set_simulation_speed(app_handle: tauri::AppHandle, speed: u64) {
+pub fn simulation_set_simulation_speed(
+    app_handle: tauri::AppHandle,
+    speed: f64,
+) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let mut sim = lock_state(&state)?;
+    sim.0.set_simulation_speed(speed)?;
+    sim.0.wake();
+    sim.0
+        .events()
+        .emit(&app_handle, SimulationEvent::SpeedChanged { speed });
+    Ok(())
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_set_unbounded(
+    app_handle: tauri::AppHandle,
+    unbounded: bool,
+) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.set_unbounded(unbounded)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_set_publish_rate(
+    app_handle: tauri::AppHandle,
+    publish_rate: u64,
+) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let mut sim = state.lock().unwrap();
-    sim.0.set_simulation_speed(speed);
+    let sim = lock_state(&state)?;
+    sim.0.set_publish_rate(publish_rate)
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn simulation_speed(app_handle: tauri::AppHandle) -> u64 {
+pub fn simulation_tick_rate(app_handle: tauri::AppHandle) -> Result<f64, SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
+    let sim = lock_state(&state)?;
+    sim.0.achieved_tick_rate()
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_speed(app_handle: tauri::AppHandle) -> Result<f64, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
     let sim = &sim.0;
 
     let binding = sim.simulation_speed();
-    let simulation_speed = binding.lock().unwrap();
+    let simulation_speed = lock_state(&binding)?;
+
+    Ok(*simulation_speed)
+}
 
-    *simulation_speed
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_time(app_handle: tauri::AppHandle) -> Result<SimulationTime, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.time()
 }
 
-fn safe_to_continue(processes: Vec<ProcessStates>, resources: Vec<GenericResource>) -> bool {
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_engine(app_handle: tauri::AppHandle) -> Result<Engine, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.engine()
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_set_engine(
+    app_handle: tauri::AppHandle,
+    engine: Engine,
+) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.set_engine(engine)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_set_seed(app_handle: tauri::AppHandle, seed: u64) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.set_seed(seed)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_set_time_step(
+    app_handle: tauri::AppHandle,
+    time_step: f64,
+) -> Result<(), SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.set_time_step(time_step)
+}
+
+pub fn safe_to_continue(processes: Vec<ProcessStates>, resources: Vec<GenericResource>) -> bool {
     if processes.len() == 0 {
         return true;
     }
@@ -431,140 +913,654 @@ fn safe_to_continue(processes: Vec<ProcessStates>, resources: Vec<GenericResourc
 }
 
 impl RunningSimulation {
-    pub fn start(&self, _app: &AppHandle) {
-        // Run the simulation in a separate thread
-        let simulation_speed_clone = self.simulation_speed.clone();
-        let last_simulation_speed_clone = self.last_simulation_speed.clone();
-        let resources_clone = self.resources.clone();
-        let processes_clone = self.processes.clone();
+    pub fn start<S: EventSink>(&self, _app: &S) -> Result<(), SimulationError> {
+        let mut worker = lock_state(&self.worker)?;
+        if worker.as_ref().is_some_and(|w| !w.is_finished()) {
+            return Ok(());
+        }
 
+        self.events.emit(_app, SimulationEvent::Started);
+
+        // Run the simulation in a separate thread
+        let simulation = self.clone();
         let app = _app.clone();
 
-        thread::spawn(move || {
-            let mut last_update = Instant::now();
-            loop {
-                // Check for the time lapse
-                loop {
-                    let simulation_speed = simulation_speed_clone.lock().unwrap();
-                    let elapsed = last_update.elapsed().as_secs_f64();
-                    let div = (1.0 / *simulation_speed as f64) as f64;
-                    if *simulation_speed == 0 || (elapsed <= div) {
-                        thread::sleep(Duration::from_micros(1));
-                    } else {
-                        last_update = Instant::now();
-                        break;
-                    }
-                    drop(simulation_speed);
+        *worker = Some(thread::spawn(move || {
+            // A panic inside a tick poisons whatever it was holding, so stop here
+            // and let the frontend decide whether to recover.
+            let error = match panic::catch_unwind(AssertUnwindSafe(|| simulation.run(&app))) {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => e,
+                Err(_) => SimulationError::WorkerDead,
+            };
+
+            simulation.events.emit(
+                &app,
+                SimulationEvent::WorkerDied {
+                    reason: error.to_string(),
+                },
+            );
+        }));
+
+        Ok(())
+    }
+
+    /// Wakes the worker so it picks up a change right away.
+    pub fn wake(&self) {
+        let _ = self.tx.send(());
+    }
+
+    fn run(&self, app: &impl EventSink) -> Result<(), SimulationError> {
+        let origin = Instant::now();
+        let mut pump = Pump::new();
+
+        loop {
+            let next = self.pump(app, &mut pump, origin.elapsed())?;
+
+            // Sleep until the next deadline, or until a command wakes us up.
+            let rx = lock_state(&self.rx)?;
+            let woken = match next {
+                Some(deadline) => rx
+                    .recv_timeout(deadline.saturating_sub(origin.elapsed()))
+                    .is_ok(),
+                None => rx.recv().is_ok(),
+            };
+
+            if woken {
+                pump.scheduler.mark_dirty();
+            }
+        }
+    }
+
+    /// Does whatever the worker loop would do at `now`, measured from the moment `pump`
+    /// was created. Returns when it has to be called again, or `None` when only a
+    /// command can give it something to do. Lets hosts without threads run the
+    /// simulation from their own timer.
+    pub fn pump(
+        &self,
+        app: &impl EventSink,
+        pump: &mut Pump,
+        now: Duration,
+    ) -> Result<Option<Duration>, SimulationError> {
+        let simulation_speed = *lock_state(&self.simulation_speed)?;
+        let unbounded = *lock_state(&self.unbounded)?;
+        let publish_rate = *lock_state(&self.publish_rate)?;
+        let engine = *lock_state(&self.engine)?;
+        let scheduler = &mut pump.scheduler;
+
+        // Commands that arrived since the last call.
+        {
+            let rx = lock_state(&self.rx)?;
+            while rx.try_recv().is_ok() {
+                scheduler.mark_dirty();
+            }
+        }
+
+        if scheduler.tick_due(now, simulation_speed, unbounded) {
+            match engine {
+                Engine::Ticked => self.tick(app, &mut pump.allocations)?,
+                Engine::DiscreteEvent => {
+                    self.step_discrete(app, &mut pump.allocations)?;
                 }
+            }
+        }
 
-                let mut simulation_speed = simulation_speed_clone.lock().unwrap();
-                let mut last_simulation_speed = last_simulation_speed_clone.lock().unwrap();
-                let resources = resources_clone.lock().unwrap();
-                let mut processes = processes_clone.lock().unwrap();
+        if scheduler.publish_due(now, publish_rate) {
+            self.publish(app, &mut pump.allocations)?;
+        }
 
-                // Prepare all processes
-                for process in processes.iter_mut() {
-                    match process {
-                        ProcessStates::Ready(ready_process) => {
-                            ready_process.prepare();
+        if let Some(rate) = scheduler.rate_due(now) {
+            *lock_state(&self.achieved_tick_rate)? = rate;
+            self.events.emit(
+                app,
+                SimulationEvent::TickRate {
+                    ticks_per_second: rate,
+                },
+            );
+        }
+
+        Ok(scheduler.next_wake(now, unbounded))
+    }
+
+    fn tick(
+        &self,
+        app: &impl EventSink,
+        allocations: &mut HashMap<String, SimulationEvent>,
+    ) -> Result<(), SimulationError> {
+        let mut simulation_speed = lock_state(&self.simulation_speed)?;
+        let mut last_simulation_speed = lock_state(&self.last_simulation_speed)?;
+        let resources = lock_state(&self.resources)?;
+        let mut processes = lock_state(&self.processes)?;
+        let mut rng = lock_state(&self.rng)?;
+        // Under the state locks, so a save never sees the clock of a half done tick.
+        lock_state(&self.clock)?.advance();
+
+        // Prepare all processes
+        for process in processes.iter_mut() {
+            match process {
+                ProcessStates::Ready(ready_process) => {
+                    let before = ready_process.resource_slot().clone();
+                    ready_process.prepare(&mut *rng);
+
+                    for (slot, previous) in ready_process.resource_slot().iter().zip(before) {
+                        // Only the latest allocation per slot survives until the next publish.
+                        if slot.current_amount() != previous.current_amount() {
+                            allocations.insert(
+                                slot.id(),
+                                SimulationEvent::Allocation {
+                                    process_id: ready_process.id(),
+                                    slot_id: slot.id(),
+                                    resource_id: slot.resource_id(),
+                                    amount: slot.current_amount(),
+                                },
+                            );
                         }
-                        _ => continue,
                     }
                 }
+                _ => continue,
+            }
+        }
 
-                match app.emit::<Vec<ProcessStates>>("processes", processes.clone()) {
-                    Ok(_) => {
-                        println!("Emitted processes");
-                    }
-                    Err(e) => {
-                        println!("Error: {}", e);
+        // Check if it is safe to continue
+        if !safe_to_continue(processes.clone(), resources.clone()) {
+            /*
+                If it is not safe to continue:
+                Try the simulation by adding one process at a time, if it is not safe
+                to continue (with this new set of processes), mark the process for deletion.
+                Continue with the next process.
+            */
+            let mut processes_to_delete = vec![];
+            let mut safe_processes = vec![];
+            for process in processes.iter() {
+                match process {
+                    ProcessStates::Ready(ready_process) => {
+                        let mut processes_to_try = safe_processes.clone();
+                        processes_to_try.push(ready_process.clone());
+
+                        // Wrap all process to a process ready state
+                        let _processes_to_try = processes_to_try
+                            .iter()
+                            .map(|p| ProcessStates::Ready(p.clone()))
+                            .collect::<Vec<ProcessStates>>();
+
+                        if safe_to_continue(_processes_to_try, resources.clone()) {
+                            safe_processes.push(ready_process.clone());
+                        } else {
+                            processes_to_delete.push(ready_process.clone().id());
+                        }
                     }
+                    _ => continue,
                 }
+            }
 
-                // Check if it is safe to continue
-                if !safe_to_continue(processes.clone(), resources.clone()) {
-                    /*
-                        If it is not safe to continue:
-                        Try the simulation by adding one process at a time, if it is not safe
-                        to continue (with this new set of processes), mark the process for deletion.
-                        Continue with the next process.
-                    */
-                    let mut processes_to_delete = vec![];
-                    let mut safe_processes = vec![];
-                    for process in processes.iter() {
-                        match process {
-                            ProcessStates::Ready(ready_process) => {
-                                let mut processes_to_try = safe_processes.clone();
-                                processes_to_try.push(ready_process.clone());
-
-                                // Wrap all process to a process ready state
-                                let _processes_to_try = processes_to_try
-                                    .iter()
-                                    .map(|p| ProcessStates::Ready(p.clone()))
-                                    .collect::<Vec<ProcessStates>>();
-
-                                if safe_to_continue(_processes_to_try, resources.clone()) {
-                                    safe_processes.push(ready_process.clone());
-                                } else {
-                                    processes_to_delete.push(ready_process.clone().id());
-                                }
-                            }
-                            _ => continue,
-                        }
-                    }
+            self.events.emit(
+                app,
+                SimulationEvent::UnsafeState {
+                    process_ids: processes_to_delete,
+                },
+            );
 
-                    match app.emit::<Vec<String>>("unsafe_state", processes_to_delete) {
-                        Ok(_) => {
-                            println!("Emitted unsafe_state");
-                        }
-                        Err(e) => {
-                            println!("Error: {}", e);
-                        }
-                    }
+            let temp = *simulation_speed;
+            *simulation_speed = 0.0;
+            *last_simulation_speed = temp;
 
-                    let temp = *simulation_speed;
-                    *simulation_speed = 0;
-                    *last_simulation_speed = temp;
-
-                    // match app.emit("simulation_stopped", 0) {
-                    //     Ok(_) => {
-                    //         println!("Emitted simulation_stopped");
-                    //     }
-                    //     Err(e) => {
-                    //         println!("Error: {}", e);
-                    //     }
-                    // }
-                } else {
-                    drop(simulation_speed);
-                    drop(resources);
-                    drop(processes);
+            self.events.emit(app, SimulationEvent::Stopped);
+        }
+
+        Ok(())
+    }
+
+    /// Jumps the clock to the next scheduled event and handles everything due then.
+    /// Returns false when nothing is scheduled.
+    fn step_discrete(
+        &self,
+        app: &impl EventSink,
+        allocations: &mut HashMap<String, SimulationEvent>,
+    ) -> Result<bool, SimulationError> {
+        let mut resources = lock_state(&self.resources)?;
+        let mut processes = lock_state(&self.processes)?;
+        let mut discrete = lock_state(&self.discrete)?;
+        let mut rng = lock_state(&self.rng)?;
+
+        let now = self.time()?.time();
+        let Some(next) = discrete.next_time(now, &processes, &mut resources) else {
+            return Ok(false);
+        };
+        lock_state(&self.clock)?.advance_to(next);
+
+        for event in discrete.step(next, &mut processes, &mut resources, &mut *rng) {
+            match event {
+                SimulationEvent::Allocation { ref slot_id, .. } => {
+                    allocations.insert(slot_id.clone(), event);
                 }
+                event => self.events.emit(app, event),
             }
+        }
+
+        // Nothing is scheduled after this, so it is reported once.
+        if discrete::deadlocked(&processes) {
+            self.events.emit(
+                app,
+                SimulationEvent::UnsafeState {
+                    process_ids: processes.iter().map(|p| p.id()).collect(),
+                },
+            );
+        }
+
+        Ok(true)
+    }
+
+    /// Runs one tick of the current engine right away, ignoring the speed, for
+    /// callers that drive the simulation themselves instead of starting the worker.
+    /// Returns false when there was nothing left to run.
+    pub fn step(&self, sink: &impl EventSink) -> Result<bool, SimulationError> {
+        let mut allocations = HashMap::new();
+        let engine = *lock_state(&self.engine)?;
+        let ran = match engine {
+            Engine::Ticked => {
+                self.tick(sink, &mut allocations)?;
+                !lock_state(&self.processes)?.is_empty()
+            }
+            Engine::DiscreteEvent => self.step_discrete(sink, &mut allocations)?,
+        };
+
+        for (_, allocation) in allocations.drain() {
+            self.events.emit(sink, allocation);
+        }
+
+        Ok(ran)
+    }
+
+    /// Sends everything that changed since the last publish as one coalesced update.
+    fn publish(
+        &self,
+        app: &impl EventSink,
+        allocations: &mut HashMap<String, SimulationEvent>,
+    ) -> Result<(), SimulationError> {
+        let resources = lock_state(&self.resources)?;
+        let processes = lock_state(&self.processes)?;
+
+        for (_, allocation) in allocations.drain() {
+            self.events.emit(app, allocation);
+        }
+
+        let time = self.time()?;
+        let published = lock_state(&self.delta)?.publish(time, &processes, &resources);
+        match published {
+            Published::Keyframe(snapshot) => {
+                self.events.emit(app, SimulationEvent::Keyframe(snapshot))
+            }
+            Published::Delta(delta) => self.events.emit(app, SimulationEvent::Delta(delta)),
+            Published::Unchanged => {}
+        }
+
+        *lock_state(&self.checkpoint)? = Some(Checkpoint {
+            time,
+            processes: processes.clone(),
+            resources: resources.clone(),
+            discrete: lock_state(&self.discrete)?.clone(),
+            rng: Some(lock_state(&self.rng)?.clone()),
         });
+
+        Ok(())
     }
 
-    pub fn stop(&self, _app: &AppHandle) {
-        let mut simulation_speed = self.simulation_speed.lock().unwrap();
-        let mut last_simulation_speed = self.last_simulation_speed.lock().unwrap();
+    pub fn set_unbounded(&self, unbounded: bool) -> Result<(), SimulationError> {
+        *lock_state(&self.unbounded)? = unbounded;
+        self.wake();
+        Ok(())
+    }
+
+    pub fn set_publish_rate(&self, publish_rate: u64) -> Result<(), SimulationError> {
+        *lock_state(&self.publish_rate)? = publish_rate.max(1);
+        self.wake();
+        Ok(())
+    }
+
+    pub fn achieved_tick_rate(&self) -> Result<f64, SimulationError> {
+        Ok(*lock_state(&self.achieved_tick_rate)?)
+    }
+
+    /// Switches engines from a clean state: the discrete engine's holdings are given
+    /// back and every process starts over as ready.
+    pub fn set_engine(&self, engine: Engine) -> Result<(), SimulationError> {
+        let mut resources = lock_state(&self.resources)?;
+        let mut processes = lock_state(&self.processes)?;
+        lock_state(&self.discrete)?.reset(&mut processes, &mut resources);
+        *lock_state(&self.engine)? = engine;
+        lock_state(&self.delta)?.request_keyframe();
+        self.wake();
+        Ok(())
+    }
+
+    pub fn engine(&self) -> Result<Engine, SimulationError> {
+        Ok(*lock_state(&self.engine)?)
+    }
+
+    /// Describes the current processes, resources and settings as a scenario.
+    pub fn scenario(&self) -> Result<Scenario, SimulationError> {
+        let settings = ScenarioSettings {
+            speed: *lock_state(&self.simulation_speed)?,
+            time_step: lock_state(&self.clock)?.time_step(),
+            engine: *lock_state(&self.engine)?,
+            publish_rate: *lock_state(&self.publish_rate)?,
+            unbounded: *lock_state(&self.unbounded)?,
+        };
+
+        let resources = lock_state(&self.resources)?;
+        let processes = lock_state(&self.processes)?;
+        Ok(Scenario::from_state(settings, &processes, &resources))
+    }
+
+    /// Throws away the current simulation and builds the one in `scenario`, with the
+    /// clock back at zero. Nothing changes if the scenario is invalid. Like
+    /// `restore_state`, the worker is paused while the state is swapped.
+    pub fn load_scenario(
+        &self,
+        app: &impl EventSink,
+        scenario: &Scenario,
+    ) -> Result<Snapshot, SimulationError> {
+        let (new_resources, new_processes) = scenario.build()?;
+        let settings = scenario.settings();
+
+        *lock_state(&self.simulation_speed)? = 0.0;
+
+        let snapshot = {
+            let mut resources = lock_state(&self.resources)?;
+            let mut processes = lock_state(&self.processes)?;
+            *resources = new_resources;
+            *processes = new_processes;
+            *lock_state(&self.discrete)? = DiscreteEngine::new();
+            {
+                let mut clock = lock_state(&self.clock)?;
+                *clock = VirtualClock::new();
+                clock.set_time_step(settings.time_step);
+            }
+
+            Snapshot::new(self.time()?, processes.clone(), resources.clone())
+        };
+
+        *lock_state(&self.engine)? = settings.engine;
+        *lock_state(&self.publish_rate)? = settings.publish_rate.max(1);
+        *lock_state(&self.unbounded)? = settings.unbounded;
+        *lock_state(&self.simulation_speed)? = settings.speed;
+        lock_state(&self.delta)?.request_keyframe();
+        self.wake();
+
+        self.events.emit(
+            app,
+            SimulationEvent::SpeedChanged {
+                speed: settings.speed,
+            },
+        );
+
+        Ok(snapshot)
+    }
+
+    /// Swaps in other processes and resources, keeping the clock and settings.
+    pub fn replace(
+        &self,
+        app: &impl EventSink,
+        new_resources: Vec<GenericResource>,
+        new_processes: Vec<ProcessStates>,
+    ) -> Result<Snapshot, SimulationError> {
+        let mut resources = lock_state(&self.resources)?;
+        let mut processes = lock_state(&self.processes)?;
+        *resources = new_resources;
+        *processes = new_processes;
+        *lock_state(&self.discrete)? = DiscreteEngine::new();
+        lock_state(&self.delta)?.request_keyframe();
+        self.wake();
+
+        Ok(Snapshot::new(
+            self.time()?,
+            processes.clone(),
+            resources.clone(),
+        ))
+    }
+
+    /// Reseeds the rng, so the same scenario rolls the same amounts from here on.
+    pub fn set_seed(&self, seed: u64) -> Result<(), SimulationError> {
+        *lock_state(&self.rng)? = SimulationRng::seed_from_u64(seed);
+        Ok(())
+    }
+
+    pub fn save_state(&self) -> Result<SaveState, SimulationError> {
+        let speed = *lock_state(&self.simulation_speed)?;
+        let last_speed = *lock_state(&self.last_simulation_speed)?;
+        let engine = *lock_state(&self.engine)?;
+        let publish_rate = *lock_state(&self.publish_rate)?;
+        let unbounded = *lock_state(&self.unbounded)?;
+
+        let resources = lock_state(&self.resources)?;
+        let processes = lock_state(&self.processes)?;
+        let discrete = lock_state(&self.discrete)?;
+        let rng = lock_state(&self.rng)?;
+
+        Ok(SaveState {
+            version: SAVE_STATE_SCHEMA.version,
+            clock: lock_state(&self.clock)?.clone(),
+            speed,
+            last_speed,
+            engine,
+            publish_rate,
+            unbounded,
+            rng: rng.clone(),
+            processes: processes.clone(),
+            resources: resources.clone(),
+            discrete: discrete.clone(),
+        })
+    }
+
+    /// Replaces the whole live state with `saved`, including the clock and rng.
+    /// The worker is paused while the state is swapped, so it never ticks the restored
+    /// processes with the old settings.
+    pub fn restore_state(
+        &self,
+        app: &impl EventSink,
+        saved: SaveState,
+    ) -> Result<Snapshot, SimulationError> {
+        validate_speed(saved.speed)?;
+        validate_time_step(saved.clock.time_step())?;
+
+        *lock_state(&self.simulation_speed)? = 0.0;
+
+        let snapshot = {
+            let mut resources = lock_state(&self.resources)?;
+            let mut processes = lock_state(&self.processes)?;
+            *resources = saved.resources;
+            *processes = saved.processes;
+            *lock_state(&self.discrete)? = saved.discrete;
+            *lock_state(&self.rng)? = saved.rng;
+            *lock_state(&self.clock)? = saved.clock;
+
+            Snapshot::new(self.time()?, processes.clone(), resources.clone())
+        };
+
+        *lock_state(&self.engine)? = saved.engine;
+        *lock_state(&self.publish_rate)? = saved.publish_rate.max(1);
+        *lock_state(&self.unbounded)? = saved.unbounded;
+        *lock_state(&self.last_simulation_speed)? = saved.last_speed;
+        *lock_state(&self.simulation_speed)? = saved.speed;
+        lock_state(&self.delta)?.request_keyframe();
+        self.wake();
+
+        self.events
+            .emit(app, SimulationEvent::SpeedChanged { speed: saved.speed });
+
+        Ok(snapshot)
+    }
+
+    /// Whether every process can still finish with the current requests.
+    pub fn is_safe(&self) -> Result<bool, SimulationError> {
+        let resources = lock_state(&self.resources)?;
+        let processes = lock_state(&self.processes)?;
+        Ok(safe_to_continue(processes.clone(), resources.clone()))
+    }
+
+    pub fn time(&self) -> Result<SimulationTime, SimulationError> {
+        Ok(lock_state(&self.clock)?.now())
+    }
+
+    /// Sets how many simulated time units pass per tick, from the next tick on.
+    pub fn set_time_step(&self, time_step: f64) -> Result<(), SimulationError> {
+        validate_time_step(time_step)?;
+        lock_state(&self.clock)?.set_time_step(time_step);
+        Ok(())
+    }
+
+    pub fn stop(&self, _app: &impl EventSink) -> Result<(), SimulationError> {
+        let mut simulation_speed = lock_state(&self.simulation_speed)?;
+        let mut last_simulation_speed = lock_state(&self.last_simulation_speed)?;
 
         let temp = *simulation_speed;
 
-        *simulation_speed = 0;
+        *simulation_speed = 0.0;
         *last_simulation_speed = temp;
+
+        self.events.emit(_app, SimulationEvent::Stopped);
+        self.wake();
+
+        Ok(())
+    }
+
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    /// Returns the full current state and makes the next tick publish a keyframe.
+    pub fn resync(&self) -> Result<Snapshot, SimulationError> {
+        let resources = lock_state(&self.resources)?;
+        let processes = lock_state(&self.processes)?;
+        lock_state(&self.delta)?.request_keyframe();
+        self.wake();
+
+        Ok(Snapshot::new(
+            self.time()?,
+            processes.clone(),
+            resources.clone(),
+        ))
+    }
+
+    pub fn health(&self) -> SimulationHealth {
+        let worker_alive = match self.worker.lock() {
+            Ok(worker) => worker.as_ref().is_some_and(|w| !w.is_finished()),
+            Err(_) => false,
+        };
+
+        let poisoned = self.simulation_speed.is_poisoned()
+            || self.last_simulation_speed.is_poisoned()
+            || self.processes.is_poisoned()
+            || self.resources.is_poisoned()
+            || self.checkpoint.is_poisoned()
+            || self.worker.is_poisoned()
+            || self.delta.is_poisoned()
+            || self.unbounded.is_poisoned()
+            || self.publish_rate.is_poisoned()
+            || self.achieved_tick_rate.is_poisoned()
+            || self.clock.is_poisoned()
+            || self.engine.is_poisoned()
+            || self.discrete.is_poisoned()
+            || self.rng.is_poisoned()
+            || self.rx.is_poisoned();
+
+        SimulationHealth {
+            worker_alive,
+            poisoned,
+        }
+    }
+
+    /// Rolls processes, resources and the clock back to the last checkpoint, clears every
+    /// poisoned lock, restarts the worker if it died and carries on at the speed it had.
+    /// Before the first publish there is nothing to go back to, so it leaves everything
+    /// as it is and fails.
+    pub fn recover(&self, app: &impl EventSink) -> Result<SimulationHealth, SimulationError> {
+        let checkpoint = match self.checkpoint.lock() {
+            Ok(checkpoint) => checkpoint.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        let checkpoint = checkpoint.ok_or(SimulationError::NoCheckpoint)?;
+        self.checkpoint.clear_poison();
+
+        let speed = *lock_and_clear(&self.simulation_speed);
+        let last_speed = *lock_and_clear(&self.last_simulation_speed);
+        *lock_and_clear(&self.processes) = checkpoint.processes;
+        *lock_and_clear(&self.resources) = checkpoint.resources;
+        lock_and_clear(&self.clock).reset_to(checkpoint.time);
+        *lock_and_clear(&self.discrete) = checkpoint.discrete;
+        match checkpoint.rng {
+            Some(rng) => *lock_and_clear(&self.rng) = rng,
+            None => self.rng.clear_poison(),
+        }
+        self.engine.clear_poison();
+        self.worker.clear_poison();
+        self.unbounded.clear_poison();
+        self.publish_rate.clear_poison();
+        self.achieved_tick_rate.clear_poison();
+        self.rx.clear_poison();
+        lock_and_clear(&self.delta).request_keyframe();
+
+        self.start(app)?;
+        *lock_state(&self.simulation_speed)? = speed;
+        *lock_state(&self.last_simulation_speed)? = last_speed;
+        self.wake();
+        self.events
+            .emit(app, SimulationEvent::SpeedChanged { speed });
+
+        let health = self.health();
+        self.events
+            .emit(app, SimulationEvent::Recovered(health.clone()));
+
+        Ok(health)
     }
 }
+
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn stop_simulation(app_handle: tauri::AppHandle) {
+pub fn stop_simulation(app_handle: tauri::AppHandle) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
-    sim.0.stop(&app_handle);
+    let sim = lock_state(&state)?;
+    sim.0.stop(&app_handle)
 }
 
+#[cfg(feature = "desktop")]
 #[tauri::command]
-pub fn start_simulation(app_handle: tauri::AppHandle) {
+pub fn start_simulation(app_handle: tauri::AppHandle) -> Result<(), SimulationError> {
     let state = app_handle.state::<Mutex<TauriSim>>();
-    let sim = state.lock().unwrap();
-    sim.0.start(&app_handle);
+    let sim = lock_state(&state)?;
+    sim.0.start(&app_handle)
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_resync(app_handle: tauri::AppHandle) -> Result<Snapshot, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_state(&state)?;
+    sim.0.resync()
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_health(app_handle: tauri::AppHandle) -> SimulationHealth {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let health = match state.lock() {
+        Ok(sim) => sim.0.health(),
+        Err(poisoned) => SimulationHealth {
+            poisoned: true,
+            ..poisoned.into_inner().0.health()
+        },
+    };
+    health
+}
+
+#[cfg(feature = "desktop")]
+#[tauri::command]
+pub fn simulation_recover(
+    app_handle: tauri::AppHandle,
+) -> Result<SimulationHealth, SimulationError> {
+    let state = app_handle.state::<Mutex<TauriSim>>();
+    let sim = lock_and_clear(&state);
+    sim.0.recover(&app_handle)
 }
 
 // remember to call `.manage(MyState::default())`
@@ -574,3 +1570,244 @@ pub fn start_simulation(app_handle: tauri::AppHandle) {
 //     let mut sim = state.lock().unwrap();
 //     sim.0 = Simulation::Stopped(Simulation::new());
 // }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::events::Recorder;
+
+    /// Three processes that always fit, so the worker never pauses on its own.
+    pub(crate) fn simulation() -> RunningSimulation {
+        let mut simulation = RunningSimulation::new();
+        let resource = GenericResource::new("Memory".to_string(), 1000, false);
+        for i in 0..3 {
+            let mut process = Process::new(
+                format!("Process {}", i),
+                GenericProcessResourceIntensity::High,
+            );
+            process.add_resource(&resource, 1 + i);
+            simulation
+                .add_process(ProcessStates::Ready(process))
+                .unwrap();
+        }
+        simulation.add_resource(resource).unwrap();
+        simulation.set_seed(7).unwrap();
+        simulation
+    }
+
+    /// An empty directory of its own for a test.
+    pub(crate) fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir()
+            .join("system-monitor-tests")
+            .join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Runs `f` on another thread and fails if it has not returned after a while.
+    pub(crate) fn within_timeout(f: impl FnOnce() + Send + 'static) {
+        let (done, finished) = channel();
+        thread::spawn(move || {
+            f();
+            let _ = done.send(());
+        });
+        assert!(
+            finished.recv_timeout(Duration::from_secs(30)).is_ok(),
+            "timed out, probably deadlocked"
+        );
+    }
+
+    #[test]
+    fn load_scenarios_while_the_worker_ticks() {
+        let simulation = simulation();
+        let sink = Recorder::default();
+        simulation.set_unbounded(true).unwrap();
+        simulation.start(&sink).unwrap();
+
+        within_timeout(move || {
+            let mut scenario = simulation.scenario().unwrap();
+            let until = Instant::now() + Duration::from_secs(2);
+            while Instant::now() < until {
+                simulation.load_scenario(&sink, &scenario).unwrap();
+                scenario = simulation.scenario().unwrap();
+            }
+            assert!(simulation.health().worker_alive);
+        });
+    }
+
+    #[test]
+    fn save_and_restore_while_the_worker_ticks() {
+        let simulation = simulation();
+        let sink = Recorder::default();
+        simulation.set_unbounded(true).unwrap();
+        simulation.start(&sink).unwrap();
+
+        let recorder = sink.clone();
+        within_timeout(move || {
+            let until = Instant::now() + Duration::from_secs(2);
+            while Instant::now() < until {
+                let saved = simulation.save_state().unwrap();
+                simulation.restore_state(&sink, saved).unwrap();
+            }
+            assert!(simulation.health().worker_alive);
+        });
+        assert!(recorder
+            .events()
+            .iter()
+            .any(|e| matches!(e, SimulationEvent::Allocation { .. })));
+    }
+
+    #[test]
+    fn a_discrete_run_with_every_process_blocked_is_a_deadlock() {
+        let mut simulation = RunningSimulation::new();
+        let mut resource = GenericResource::new("Lock".to_string(), 10, true);
+        resource.set_free_amount(0);
+        for i in 0..2 {
+            let mut process = Process::new(
+                format!("Process {}", i),
+                GenericProcessResourceIntensity::High,
+            );
+            process.add_resource(&resource, 1);
+            simulation
+                .add_process(ProcessStates::Ready(process))
+                .unwrap();
+        }
+        simulation.add_resource(resource).unwrap();
+        simulation.set_engine(Engine::DiscreteEvent).unwrap();
+
+        let sink = Recorder::default();
+        let mut steps = 0;
+        while simulation.step(&sink).unwrap() {
+            steps += 1;
+            assert!(steps < 100, "the deadlocked run never ran out of events");
+        }
+
+        let unsafe_states: Vec<_> = sink
+            .events()
+            .into_iter()
+            .filter_map(|event| match event {
+                SimulationEvent::UnsafeState { process_ids } => Some(process_ids),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(unsafe_states.len(), 1);
+        assert_eq!(unsafe_states[0].len(), 2);
+    }
+
+    #[test]
+    fn recover_rolls_back_to_the_last_publish() {
+        let mut simulation = simulation();
+        let sink = Recorder::default();
+        simulation.set_simulation_speed(30.0).unwrap();
+        simulation.publish(&sink, &mut HashMap::new()).unwrap();
+        let published = lock_state(&simulation.processes).unwrap().clone();
+        for _ in 0..5 {
+            simulation.step(&sink).unwrap();
+        }
+
+        let processes = simulation.processes.clone();
+        let _ = thread::spawn(move || {
+            let _processes = processes.lock().unwrap();
+            panic!("poisoning the processes");
+        })
+        .join();
+        assert!(simulation.health().poisoned);
+
+        let health = simulation.recover(&sink).unwrap();
+        assert!(!health.poisoned);
+        assert!(health.worker_alive);
+        assert_eq!(*lock_state(&simulation.processes).unwrap(), published);
+        assert_eq!(*lock_state(&simulation.simulation_speed).unwrap(), 30.0);
+        simulation.stop(&sink).unwrap();
+    }
+
+    #[test]
+    fn recover_before_the_first_publish_changes_nothing() {
+        let simulation = simulation();
+        let before = lock_state(&simulation.processes).unwrap().clone();
+
+        assert_eq!(
+            simulation.recover(&Recorder::default()).unwrap_err(),
+            SimulationError::NoCheckpoint
+        );
+        assert_eq!(*lock_state(&simulation.processes).unwrap(), before);
+        assert!(!simulation.health().worker_alive);
+    }
+
+    /// The shared simulation plus a "Disk" held by the first and last process only.
+    fn simulation_with_disk() -> (RunningSimulation, String, Vec<String>) {
+        let mut simulation = simulation();
+        let disk = GenericResource::new("Disk".to_string(), 100, false);
+        let mut holders = vec![];
+        {
+            let mut processes = lock_state(&simulation.processes).unwrap();
+            for index in [0, 2] {
+                processes[index].process_mut().add_resource(&disk, 10);
+                holders.push(processes[index].id());
+            }
+        }
+        let disk_id = disk.id();
+        simulation.add_resource(disk).unwrap();
+        (simulation, disk_id, holders)
+    }
+
+    fn slots_for(simulation: &RunningSimulation, resource_id: &str) -> usize {
+        lock_state(&simulation.processes)
+            .unwrap()
+            .iter()
+            .flat_map(|p| p.process().resource_slot().iter())
+            .filter(|slot| slot.resource_id() == resource_id)
+            .count()
+    }
+
+    #[test]
+    fn refuse_keeps_a_resource_in_use() {
+        let (mut simulation, disk_id, holders) = simulation_with_disk();
+
+        assert_eq!(
+            simulation
+                .remove_resource_by_id(disk_id.clone(), ResourceRemovalPolicy::Refuse)
+                .unwrap_err(),
+            SimulationError::ResourceInUse(holders)
+        );
+        assert!(simulation
+            .get_resource_by_id(disk_id.clone())
+            .unwrap()
+            .is_some());
+        assert_eq!(slots_for(&simulation, &disk_id), 2);
+    }
+
+    #[test]
+    fn release_slots_frees_every_holder() {
+        let (mut simulation, disk_id, holders) = simulation_with_disk();
+
+        let removal = simulation
+            .remove_resource_by_id(disk_id.clone(), ResourceRemovalPolicy::ReleaseSlots)
+            .unwrap();
+        assert_eq!(removal.released_processes, holders);
+        assert_eq!(removal.released_slots.len(), 2);
+        assert!(removal.deleted_processes.is_empty());
+        assert_eq!(slots_for(&simulation, &disk_id), 0);
+        assert_eq!(lock_state(&simulation.processes).unwrap().len(), 3);
+        assert!(simulation.get_resource_by_id(disk_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn delete_processes_drops_only_the_holders() {
+        let (mut simulation, disk_id, holders) = simulation_with_disk();
+        let kept = lock_state(&simulation.processes).unwrap()[1].id();
+
+        let removal = simulation
+            .remove_resource_by_id(disk_id.clone(), ResourceRemovalPolicy::DeleteProcesses)
+            .unwrap();
+        assert_eq!(removal.deleted_processes, holders);
+        let remaining = lock_state(&simulation.processes)
+            .unwrap()
+            .iter()
+            .map(|p| p.id())
+            .collect::<Vec<String>>();
+        assert_eq!(remaining, vec![kept]);
+        assert!(simulation.get_resource_by_id(disk_id).unwrap().is_none());
+    }
+}
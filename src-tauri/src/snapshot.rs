@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use crate::clock::SimulationTime;
+use crate::generic_process::ProcessStates;
+use crate::generic_resource::GenericResource;
+
+/// Publishes between two full keyframes, unchanged ones included, so a consumer that
+/// missed a delta catches up.
+pub const KEYFRAME_INTERVAL: u64 = 60;
+
+#[derive(Clone, serde::Serialize, Debug, Default)]
+pub struct Snapshot {
+    time: SimulationTime,
+    processes: Vec<ProcessStates>,
+    resources: Vec<GenericResource>,
+}
+
+impl Snapshot {
+    pub fn new(
+        time: SimulationTime,
+        processes: Vec<ProcessStates>,
+        resources: Vec<GenericResource>,
+    ) -> Self {
+        Self {
+            time,
+            processes,
+            resources,
+        }
+    }
+}
+
+/// Changes since the previous snapshot, keyed by process and resource id.
+#[derive(Clone, serde::Serialize, Debug, Default)]
+pub struct SnapshotDelta {
+    time: SimulationTime,
+    added_processes: Vec<ProcessStates>,
+    changed_processes: Vec<ProcessStates>,
+    removed_processes: Vec<String>,
+    added_resources: Vec<GenericResource>,
+    changed_resources: Vec<GenericResource>,
+    removed_resources: Vec<String>,
+}
+
+impl SnapshotDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added_processes.is_empty()
+            && self.changed_processes.is_empty()
+            && self.removed_processes.is_empty()
+            && self.added_resources.is_empty()
+            && self.changed_resources.is_empty()
+            && self.removed_resources.is_empty()
+    }
+}
+
+pub enum Published {
+    Keyframe(Snapshot),
+    Delta(SnapshotDelta),
+    Unchanged,
+}
+
+// `GenericResource` compares by address, so compare what the frontend actually shows.
+fn same_resource(a: &GenericResource, b: &GenericResource) -> bool {
+    a.name() == b.name()
+        && a.total_amount() == b.total_amount()
+        && a.free_amount() == b.free_amount()
+        && a.blocking() == b.blocking()
+}
+
+/// Remembers what was last published and turns the next state into a delta.
+#[derive(Default)]
+pub struct DeltaTracker {
+    processes: HashMap<String, ProcessStates>,
+    resources: HashMap<String, GenericResource>,
+    since_keyframe: u64,
+    keyframe_requested: bool,
+}
+
+impl DeltaTracker {
+    pub fn new() -> Self {
+        Self {
+            keyframe_requested: true,
+            ..Self::default()
+        }
+    }
+
+    /// Makes the next call to `publish` send a full keyframe.
+    pub fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
+    }
+
+    pub fn publish(
+        &mut self,
+        time: SimulationTime,
+        processes: &[ProcessStates],
+        resources: &[GenericResource],
+    ) -> Published {
+        let keyframe = self.keyframe_requested || self.since_keyframe >= KEYFRAME_INTERVAL;
+        let current_processes: HashMap<String, ProcessStates> =
+            processes.iter().map(|p| (p.id(), p.clone())).collect();
+        let current_resources: HashMap<String, GenericResource> =
+            resources.iter().map(|r| (r.id(), r.clone())).collect();
+
+        let mut delta = SnapshotDelta {
+            time,
+            ..SnapshotDelta::default()
+        };
+        if !keyframe {
+            for process in processes {
+                match self.processes.get(&process.id()) {
+                    None => delta.added_processes.push(process.clone()),
+                    Some(previous) if previous != process => {
+                        delta.changed_processes.push(process.clone())
+                    }
+                    Some(_) => {}
+                }
+            }
+            for resource in resources {
+                match self.resources.get(&resource.id()) {
+                    None => delta.added_resources.push(resource.clone()),
+                    Some(previous) if !same_resource(previous, resource) => {
+                        delta.changed_resources.push(resource.clone())
+                    }
+                    Some(_) => {}
+                }
+            }
+            delta.removed_processes = self
+                .processes
+                .keys()
+                .filter(|id| !current_processes.contains_key(*id))
+                .cloned()
+                .collect();
+            delta.removed_resources = self
+                .resources
+                .keys()
+                .filter(|id| !current_resources.contains_key(*id))
+                .cloned()
+                .collect();
+        }
+
+        self.processes = current_processes;
+        self.resources = current_resources;
+
+        if keyframe {
+            self.keyframe_requested = false;
+            self.since_keyframe = 0;
+            return Published::Keyframe(Snapshot::new(
+                time,
+                processes.to_vec(),
+                resources.to_vec(),
+            ));
+        }
+
+        self.since_keyframe += 1;
+        if delta.is_empty() {
+            return Published::Unchanged;
+        }
+        Published::Delta(delta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic_process::{GenericProcessResourceIntensity, Process};
+
+    fn process(name: &str) -> ProcessStates {
+        ProcessStates::Ready(Process::new(
+            name.to_string(),
+            GenericProcessResourceIntensity::Low,
+        ))
+    }
+
+    #[test]
+    fn only_changes_are_sent_between_keyframes() {
+        let mut tracker = DeltaTracker::new();
+        let time = SimulationTime::default();
+        let kept = process("kept");
+        let removed = process("removed");
+        let mut resource = GenericResource::new("Memory".to_string(), 10, false);
+
+        let published =
+            tracker.publish(time, &[kept.clone(), removed.clone()], &[resource.clone()]);
+        assert!(matches!(published, Published::Keyframe(_)));
+        let published =
+            tracker.publish(time, &[kept.clone(), removed.clone()], &[resource.clone()]);
+        assert!(matches!(published, Published::Unchanged));
+
+        let added = process("added");
+        resource.set_free_amount(4);
+        let Published::Delta(delta) =
+            tracker.publish(time, &[kept.clone(), added.clone()], &[resource.clone()])
+        else {
+            panic!("expected a delta");
+        };
+        assert_eq!(delta.added_processes, vec![added]);
+        assert!(delta.changed_processes.is_empty());
+        assert_eq!(delta.removed_processes, vec![removed.id()]);
+        assert_eq!(delta.changed_resources.len(), 1);
+        assert_eq!(delta.changed_resources[0].free_amount(), 4);
+        assert!(delta.added_resources.is_empty());
+        assert!(delta.removed_resources.is_empty());
+    }
+
+    #[test]
+    fn keyframes_come_on_request_and_every_interval() {
+        let mut tracker = DeltaTracker::new();
+        let time = SimulationTime::default();
+        let processes = [process("idle")];
+
+        assert!(matches!(
+            tracker.publish(time, &processes, &[]),
+            Published::Keyframe(_)
+        ));
+        for _ in 0..KEYFRAME_INTERVAL {
+            assert!(matches!(
+                tracker.publish(time, &processes, &[]),
+                Published::Unchanged
+            ));
+        }
+        assert!(matches!(
+            tracker.publish(time, &processes, &[]),
+            Published::Keyframe(_)
+        ));
+
+        tracker.request_keyframe();
+        assert!(matches!(
+            tracker.publish(time, &processes, &[]),
+            Published::Keyframe(_)
+        ));
+        assert!(matches!(
+            tracker.publish(time, &processes, &[]),
+            Published::Unchanged
+        ));
+    }
+}
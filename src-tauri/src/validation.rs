@@ -0,0 +1,248 @@
+use std::fmt;
+
+use crate::generic_process::{AllProcessTraits, ProcessStates};
+use crate::generic_resource::GenericResource;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    EmptyName,
+    AmountExceedsTotal { amount: u64, total_amount: u64 },
+    DuplicateSlot { resource_id: String },
+    TotalBelowAllocated { total_amount: u64, allocated: u64 },
+    InvalidSpeed { speed: f64 },
+    InvalidTimeStep { time_step: f64 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::EmptyName => write!(f, "Name cannot be empty"),
+            ValidationError::AmountExceedsTotal {
+                amount,
+                total_amount,
+            } => write!(
+                f,
+                "Amount {} exceeds the resource total of {}",
+                amount, total_amount
+            ),
+            ValidationError::DuplicateSlot { resource_id } => {
+                write!(f, "Process already has a slot for resource {}", resource_id)
+            }
+            ValidationError::TotalBelowAllocated {
+                total_amount,
+                allocated,
+            } => write!(
+                f,
+                "Total amount {} is below the {} already allocated",
+                total_amount, allocated
+            ),
+            ValidationError::InvalidSpeed { speed } => {
+                write!(
+                    f,
+                    "Speed must be a finite number of at least 0, got {}",
+                    speed
+                )
+            }
+            ValidationError::InvalidTimeStep { time_step } => {
+                write!(
+                    f,
+                    "Time step must be a finite number above 0, got {}",
+                    time_step
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl serde::Serialize for ValidationError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub fn validate_name(name: &str) -> Result<(), ValidationError> {
+    if name.trim().is_empty() {
+        return Err(ValidationError::EmptyName);
+    }
+    Ok(())
+}
+
+pub fn validate_speed(speed: f64) -> Result<(), ValidationError> {
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(ValidationError::InvalidSpeed { speed });
+    }
+    Ok(())
+}
+
+pub fn validate_time_step(time_step: f64) -> Result<(), ValidationError> {
+    if !time_step.is_finite() || time_step <= 0.0 {
+        return Err(ValidationError::InvalidTimeStep { time_step });
+    }
+    Ok(())
+}
+
+/// Checks the base amount, not what `prepare` rolls from it. A base larger than the
+/// resource can never be satisfied, so `safe_to_continue` would report every state as
+/// unsafe. Rolls go up to just under three times the base and may still not fit,
+/// which is how the simulation reaches unsafe states at all. A second slot for the
+/// same resource would be ignored by `safe_to_continue`, since it only looks at the
+/// first match.
+pub fn validate_slot(
+    process: &dyn AllProcessTraits,
+    resource: &GenericResource,
+    amount: u64,
+) -> Result<(), ValidationError> {
+    if amount > resource.total_amount() {
+        return Err(ValidationError::AmountExceedsTotal {
+            amount,
+            total_amount: resource.total_amount(),
+        });
+    }
+
+    if process
+        .resource_slot()
+        .iter()
+        .any(|slot| slot.resource_id() == resource.id())
+    {
+        return Err(ValidationError::DuplicateSlot {
+            resource_id: resource.id(),
+        });
+    }
+
+    Ok(())
+}
+
+/// The new total has to cover what is already taken from the resource and the
+/// largest slot any process holds on it.
+pub fn validate_total_amount(
+    resource: &GenericResource,
+    total_amount: u64,
+    processes: &[ProcessStates],
+) -> Result<(), ValidationError> {
+    let in_use = resource
+        .total_amount()
+        .saturating_sub(resource.free_amount());
+    let largest_slot = processes
+        .iter()
+        .flat_map(|p| p.process().resource_slot().iter())
+        .filter(|slot| slot.resource_id() == resource.id())
+        .map(|slot| slot.base_amount())
+        .max()
+        .unwrap_or(0);
+
+    let allocated = in_use.max(largest_slot);
+    if total_amount < allocated {
+        return Err(ValidationError::TotalBelowAllocated {
+            total_amount,
+            allocated,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generic_process::{GenericProcessResourceIntensity, Process};
+
+    fn process_with_slot(resource: &GenericResource, amount: u64) -> ProcessStates {
+        let mut process = Process::new("Editor".to_string(), GenericProcessResourceIntensity::Low);
+        process.add_resource(resource, amount);
+        ProcessStates::Ready(process)
+    }
+
+    #[test]
+    fn names_need_more_than_whitespace() {
+        assert_eq!(validate_name(""), Err(ValidationError::EmptyName));
+        assert_eq!(validate_name(" \t\n"), Err(ValidationError::EmptyName));
+        assert_eq!(validate_name(" Memory "), Ok(()));
+    }
+
+    #[test]
+    fn speed_may_be_zero_but_not_negative() {
+        // A speed of zero is how the simulation is paused.
+        assert_eq!(validate_speed(0.0), Ok(()));
+        assert_eq!(validate_speed(2.5), Ok(()));
+        assert_eq!(
+            validate_speed(-1.0),
+            Err(ValidationError::InvalidSpeed { speed: -1.0 })
+        );
+        assert!(validate_speed(f64::NAN).is_err());
+        assert!(validate_speed(f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn time_step_has_to_be_positive() {
+        assert_eq!(validate_time_step(0.5), Ok(()));
+        assert_eq!(
+            validate_time_step(0.0),
+            Err(ValidationError::InvalidTimeStep { time_step: 0.0 })
+        );
+        assert_eq!(
+            validate_time_step(-0.5),
+            Err(ValidationError::InvalidTimeStep { time_step: -0.5 })
+        );
+        assert!(validate_time_step(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn slot_has_to_fit_and_be_the_only_one_for_its_resource() {
+        let resource = GenericResource::new("Memory".to_string(), 100, false);
+        let other = GenericResource::new("Disk".to_string(), 100, false);
+        let process = process_with_slot(&resource, 10);
+
+        assert_eq!(validate_slot(process.process(), &other, 100), Ok(()));
+        assert_eq!(
+            validate_slot(process.process(), &other, 101),
+            Err(ValidationError::AmountExceedsTotal {
+                amount: 101,
+                total_amount: 100
+            })
+        );
+        assert_eq!(
+            validate_slot(process.process(), &resource, 5),
+            Err(ValidationError::DuplicateSlot {
+                resource_id: resource.id()
+            })
+        );
+    }
+
+    #[test]
+    fn total_has_to_cover_what_is_in_use() {
+        let mut resource = GenericResource::new("Memory".to_string(), 100, false);
+        resource.set_free_amount(40);
+
+        assert_eq!(validate_total_amount(&resource, 60, &[]), Ok(()));
+        assert_eq!(
+            validate_total_amount(&resource, 59, &[]),
+            Err(ValidationError::TotalBelowAllocated {
+                total_amount: 59,
+                allocated: 60
+            })
+        );
+    }
+
+    #[test]
+    fn total_has_to_cover_the_largest_slot() {
+        let resource = GenericResource::new("Memory".to_string(), 100, false);
+        let processes = vec![
+            process_with_slot(&resource, 30),
+            process_with_slot(&resource, 80),
+        ];
+
+        assert_eq!(validate_total_amount(&resource, 80, &processes), Ok(()));
+        assert_eq!(
+            validate_total_amount(&resource, 79, &processes),
+            Err(ValidationError::TotalBelowAllocated {
+                total_amount: 79,
+                allocated: 80
+            })
+        );
+    }
+}
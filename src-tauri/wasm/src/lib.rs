@@ -0,0 +1,416 @@
+//! WebAssembly build of the simulation engine, so the frontend can run as a static
+//! page with no desktop backend.
+//!
+//! `invoke` takes the same command names and arguments as the Tauri commands and
+//! answers with the same JSON, and `events` hands out the same envelopes the app
+//! emits on the `simulation` channel. There are no threads in the browser, so the
+//! page calls `pump` from its own timer where the desktop app runs a worker.
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use app_lib::batch::{apply_batch, BatchResult, Mutation};
+use app_lib::events::{EventEnvelope, EventSink, SimulationEvent};
+use app_lib::generic_process::{self, GenericProcessResourceIntensity, ProcessStates};
+use app_lib::generic_resource::{self, GenericResource};
+use app_lib::save_state::SaveState;
+use app_lib::scenario::Scenario;
+use app_lib::simulation::{
+    lock_state, AllSimulationTrait, Pump, ResourceRemovalPolicy, RunningSimulation, SimulationError,
+};
+use app_lib::validation::validate_name;
+
+/// Keeps the events emitted since the page last asked for them.
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<EventEnvelope>>>);
+
+impl EventSink for Collector {
+    fn send(&self, envelope: EventEnvelope) {
+        if let Ok(mut events) = self.0.lock() {
+            events.push(envelope);
+        }
+    }
+}
+
+fn not_available(command: &str) -> String {
+    format!("'{}' is not available in the browser", command)
+}
+
+fn arg<T: DeserializeOwned>(command: &str, args: &Value, name: &str) -> Result<T, String> {
+    // Missing arguments read as null, so optional ones come out as `None`.
+    let value = args.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value)
+        .map_err(|e| format!("invalid args `{}` for command `{}`: {}", name, command, e))
+}
+
+fn reply<T: Serialize, E: ToString>(result: Result<T, E>) -> Result<Value, String> {
+    let value = result.map_err(|e| e.to_string())?;
+    serde_json::to_value(value).map_err(|e| e.to_string())
+}
+
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsValue> {
+    let text = serde_json::to_string(value).map_err(|e| JsValue::from(e.to_string()))?;
+    js_sys::JSON::parse(&text)
+}
+
+#[wasm_bindgen(start)]
+fn start() {
+    console_error_panic_hook::set_once();
+}
+
+#[wasm_bindgen]
+pub struct Simulation {
+    simulation: RunningSimulation,
+    events: Collector,
+    pump: Pump,
+    /// Stands in for the worker thread: `pump` does nothing until the simulation is started.
+    running: bool,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl Simulation {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Simulation {
+            simulation: RunningSimulation::new(),
+            events: Collector::default(),
+            pump: Pump::new(),
+            running: false,
+        }
+    }
+
+    /// Runs the same command the desktop app would, with the same camelCase arguments.
+    /// Rejects with the same error message, too.
+    pub fn invoke(&mut self, command: &str, args: JsValue) -> Result<JsValue, JsValue> {
+        let args: Value = if args.is_undefined() || args.is_null() {
+            Value::Null
+        } else {
+            let text = js_sys::JSON::stringify(&args)?;
+            serde_json::from_str(&String::from(text)).map_err(|e| JsValue::from(e.to_string()))?
+        };
+
+        match self.dispatch(command, &args) {
+            Ok(value) => to_js(&value),
+            Err(error) => Err(JsValue::from(error)),
+        }
+    }
+
+    /// Does whatever the desktop worker would do at `now`, in milliseconds from
+    /// `performance.now()`. Returns when it wants to be called again, or `undefined`
+    /// when only a command can give it something to do.
+    pub fn pump(&mut self, now: f64) -> Result<Option<f64>, JsValue> {
+        if !self.running {
+            return Ok(None);
+        }
+
+        let now = Duration::from_secs_f64(now.max(0.0) / 1000.0);
+        let next = self
+            .simulation
+            .pump(&self.events, &mut self.pump, now)
+            .map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(next.map(|next| next.as_secs_f64() * 1000.0))
+    }
+
+    /// Takes every event emitted since the last call, oldest first.
+    pub fn events(&self) -> Result<JsValue, JsValue> {
+        let events = match self.events.0.lock() {
+            Ok(mut events) => std::mem::take(&mut *events),
+            Err(_) => vec![],
+        };
+        to_js(&events)
+    }
+}
+
+impl Simulation {
+    /// Single mutations go through a batch so they are validated the same way.
+    fn apply(&self, mutation: Mutation) -> Result<BatchResult, SimulationError> {
+        let result = apply_batch(
+            &self.simulation.resources(),
+            &self.simulation.processes(),
+            vec![mutation],
+        )
+        .map_err(|e| match e {
+            // The index of the only mutation means nothing to the caller.
+            SimulationError::BatchFailed { error, .. } => *error,
+            e => e,
+        })?;
+        self.simulation.wake();
+        Ok(result)
+    }
+
+    fn find_process<T>(
+        &self,
+        process_id: &str,
+        f: impl FnOnce(&ProcessStates) -> T,
+    ) -> Result<T, SimulationError> {
+        let processes = self.simulation.processes();
+        let processes = lock_state(&processes)?;
+        processes
+            .iter()
+            .find(|p| p.id() == process_id)
+            .map(f)
+            .ok_or(SimulationError::ProcessNotFound)
+    }
+
+    fn update_process(
+        &self,
+        f: impl FnOnce(&mut [ProcessStates]) -> Result<(), SimulationError>,
+    ) -> Result<(), SimulationError> {
+        let processes = self.simulation.processes();
+        let mut processes = lock_state(&processes)?;
+        f(&mut processes)
+    }
+
+    fn dispatch(&mut self, command: &str, args: &Value) -> Result<Value, String> {
+        let sim = &mut self.simulation;
+        let events = &self.events;
+
+        match command {
+            "create_resource" => reply(generic_resource::create_resource(
+                arg(command, args, "name")?,
+                arg(command, args, "totalAmount")?,
+                arg(command, args, "blocking")?,
+            )),
+            "get_resource_name" => reply::<_, String>(Ok(generic_resource::get_resource_name(
+                arg(command, args, "resource")?,
+            ))),
+            "set_resource_name" => reply(generic_resource::set_resource_name(
+                arg(command, args, "resource")?,
+                arg(command, args, "name")?,
+            )),
+            "get_resource_total_amount" => reply::<_, String>(Ok(
+                generic_resource::get_resource_total_amount(arg(command, args, "resource")?),
+            )),
+            "set_resource_total_amount" => reply(generic_resource::set_resource_total_amount(
+                arg(command, args, "resource")?,
+                arg(command, args, "totalAmount")?,
+            )),
+            "get_resource_free_amount" => reply::<_, String>(Ok(
+                generic_resource::get_resource_free_amount(arg(command, args, "resource")?),
+            )),
+
+            "create_process" => {
+                let created = self
+                    .apply(Mutation::CreateProcess {
+                        key: "process".to_string(),
+                        name: arg(command, args, "name")?,
+                        resource_intensity: arg(command, args, "resourceIntensity")?,
+                    })
+                    .map_err(|e| e.to_string())?;
+                let id = created.created("process").cloned().unwrap_or_default();
+                reply(self.find_process(&id, |p| match p {
+                    ProcessStates::Ready(process) => Some(process.clone()),
+                    _ => None,
+                }))
+            }
+            "process_add_resource" => reply(
+                self.apply(Mutation::AddSlot {
+                    process: arg(command, args, "processId")?,
+                    resource: arg(command, args, "resourceId")?,
+                    amount: arg(command, args, "amount")?,
+                })
+                .map(|_| ()),
+            ),
+            "process_remove_resource" => reply(
+                self.apply(Mutation::RemoveSlot {
+                    process: arg(command, args, "processId")?,
+                    resource: arg(command, args, "resourceId")?,
+                })
+                .map(|_| ()),
+            ),
+            "process_get_resource_intensity" => {
+                let process_id: String = arg(command, args, "processId")?;
+                reply(self.find_process(&process_id, |p| *p.process().resource_intensity()))
+            }
+            "process_get_name" => {
+                let process_id: String = arg(command, args, "processId")?;
+                reply(self.find_process(&process_id, |p| p.process().name()))
+            }
+            "process_set_name" => {
+                let process_id: String = arg(command, args, "processId")?;
+                let name: String = arg(command, args, "name")?;
+                reply(self.update_process(|processes| {
+                    generic_process::set_process_name(processes, &process_id, name)
+                }))
+            }
+            "process_set_resource_intensity" => {
+                let process_id: String = arg(command, args, "processId")?;
+                let resource_intensity: GenericProcessResourceIntensity =
+                    arg(command, args, "resourceIntensity")?;
+                reply(self.update_process(|processes| {
+                    generic_process::set_process_resource_intensity(
+                        processes,
+                        &process_id,
+                        resource_intensity,
+                    )
+                }))
+            }
+
+            "simulation_add_process" => reply(
+                self.apply(Mutation::CreateProcess {
+                    key: "process".to_string(),
+                    name: arg(command, args, "name")?,
+                    resource_intensity: arg(command, args, "resourceIntensity")?,
+                })
+                .map(|_| ()),
+            ),
+            "simulation_add_resource" => {
+                let mut resource: GenericResource = arg(command, args, "resource")?;
+                validate_name(&resource.name())
+                    .map_err(|e| SimulationError::from(e).to_string())?;
+                // Never trust a free amount above the total, like the desktop command.
+                resource.set_free_amount(resource.free_amount().min(resource.total_amount()));
+                let result = sim.add_resource(resource);
+                sim.wake();
+                reply(result)
+            }
+            "simulation_remove_process" => {
+                let process_id: String = arg(command, args, "processId")?;
+                let result = match sim.get_process_by_id(process_id) {
+                    Ok(Some(process)) => sim.remove_process(&process),
+                    Ok(None) => Ok(()),
+                    Err(e) => Err(e),
+                };
+                sim.wake();
+                reply(result)
+            }
+            "simulation_remove_resource" => {
+                let resource_id: String = arg(command, args, "resourceId")?;
+                let policy: Option<ResourceRemovalPolicy> = arg(command, args, "policy")?;
+                let result = sim.remove_resource_by_id(resource_id, policy.unwrap_or_default());
+                sim.wake();
+                reply(result)
+            }
+            "simulation_processes" => {
+                let processes = sim.processes();
+                let processes = lock_state(&processes).map_err(|e| e.to_string())?;
+                reply::<_, String>(Ok(processes.clone()))
+            }
+            "simulation_resources" => {
+                let resources = sim.resources();
+                let resources = lock_state(&resources).map_err(|e| e.to_string())?;
+                reply::<_, String>(Ok(resources.clone()))
+            }
+            "simulation_set_resource_total_amount" => {
+                let resource_id: String = arg(command, args, "resourceId")?;
+                let result = self
+                    .apply(Mutation::SetResourceTotalAmount {
+                        resource: resource_id.clone(),
+                        total_amount: arg(command, args, "totalAmount")?,
+                    })
+                    .and_then(|_| {
+                        self.simulation
+                            .get_resource_by_id(resource_id)?
+                            .ok_or(SimulationError::ResourceNotFound)
+                    });
+                reply(result)
+            }
+            "simulation_set_simulation_speed" => {
+                let speed: f64 = arg(command, args, "speed")?;
+                sim.set_simulation_speed(speed).map_err(|e| e.to_string())?;
+                sim.wake();
+                sim.events()
+                    .emit(events, SimulationEvent::SpeedChanged { speed });
+                reply::<_, String>(Ok(()))
+            }
+            "simulation_speed" => {
+                let speed = sim.simulation_speed();
+                let speed = lock_state(&speed).map_err(|e| e.to_string())?;
+                reply::<_, String>(Ok(*speed))
+            }
+            "simulation_set_unbounded" => {
+                reply(sim.set_unbounded(arg(command, args, "unbounded")?))
+            }
+            "simulation_set_publish_rate" => {
+                reply(sim.set_publish_rate(arg(command, args, "publishRate")?))
+            }
+            "simulation_tick_rate" => reply(sim.achieved_tick_rate()),
+            "simulation_time" => reply(sim.time()),
+            "simulation_set_time_step" => reply(sim.set_time_step(arg(command, args, "timeStep")?)),
+            "simulation_engine" => reply(sim.engine()),
+            "simulation_set_engine" => reply(sim.set_engine(arg(command, args, "engine")?)),
+            "simulation_set_seed" => reply(sim.set_seed(arg(command, args, "seed")?)),
+            "stop_simulation" => reply(sim.stop(events)),
+            "start_simulation" => {
+                if !self.running {
+                    self.running = true;
+                    sim.events().emit(events, SimulationEvent::Started);
+                }
+                reply::<_, String>(Ok(()))
+            }
+            "simulation_resync" => reply(sim.resync()),
+            "simulation_health" => reply::<_, String>(Ok(sim.health().pumped(self.running))),
+            // A panic aborts the whole module in the browser, so there is never a dead
+            // worker or a poisoned lock left to recover from.
+            "simulation_recover" => reply::<_, String>(Ok(sim.health().pumped(self.running))),
+            "simulation_apply_batch" => {
+                let mutations: Vec<Mutation> = arg(command, args, "mutations")?;
+                let result = apply_batch(&sim.resources(), &sim.processes(), mutations);
+                sim.wake();
+                reply(result)
+            }
+
+            // There is no file system, so files are passed in and handed back as text.
+            // `path` only picks the format, like the extension of a file would.
+            "simulation_load_scenario" => {
+                let path: String = arg(command, args, "path")?;
+                let contents: String = arg(command, args, "contents")?;
+                let scenario = Scenario::parse(Path::new(&path), &contents)
+                    .map_err(|e| SimulationError::from(e).to_string())?;
+                reply(sim.load_scenario(events, &scenario))
+            }
+            "simulation_save_scenario" => {
+                let path: String = arg(command, args, "path")?;
+                let scenario = sim.scenario().map_err(|e| e.to_string())?;
+                reply(
+                    scenario
+                        .render(Path::new(&path))
+                        .map_err(|e| SimulationError::from(e).to_string()),
+                )
+            }
+            "simulation_save_state" => {
+                let saved = sim.save_state().map_err(|e| e.to_string())?;
+                reply(
+                    saved
+                        .render()
+                        .map_err(|e| SimulationError::from(e).to_string()),
+                )
+            }
+            "simulation_restore_state" => {
+                let contents: String = arg(command, args, "contents")?;
+                let saved = SaveState::parse(&contents)
+                    .map_err(|e| SimulationError::from(e).to_string())?;
+                reply(sim.restore_state(events, saved))
+            }
+
+            // Nothing is ever autosaved in the browser.
+            "autosave_list" => reply::<_, String>(Ok(Vec::<Value>::new())),
+            "simulation_import_system"
+            | "system_monitor_start"
+            | "system_monitor_stop"
+            | "system_lock_report"
+            | "system_cgroup_report"
+            | "simulation_import_cgroup"
+            | "autosave_settings"
+            | "autosave_set_settings"
+            | "autosave_restore"
+            | "api_server_start"
+            | "api_server_stop"
+            | "api_server_status" => Err(not_available(command)),
+
+            _ => Err(format!("Command {} not found", command)),
+        }
+    }
+}